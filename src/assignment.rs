@@ -0,0 +1,393 @@
+// Matching rows (ships) to columns (goals) of a weight matrix so that the total
+// weight is as large as possible. Each row gets at most one column and each column
+// at most one row; when there are more rows than columns some rows get None.
+use std::collections::VecDeque;
+use std::f64;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AssignMethod {
+	Greedy, Hungarian, MinCostFlow,
+}
+
+impl AssignMethod {
+	pub fn from_name(name: &str) -> Option<AssignMethod> {
+		match name {
+			"Greedy" => Some(AssignMethod::Greedy),
+			"Hungarian" => Some(AssignMethod::Hungarian),
+			"MinCostFlow" => Some(AssignMethod::MinCostFlow),
+			_ => None,
+		}
+	}
+}
+
+pub fn total_weight(weights: &[Vec<f32>], assignment: &[Option<usize>]) -> f64 {
+	assignment.iter().enumerate()
+		.filter_map(|(row, col)| col.map(|c| weights[row][c] as f64))
+		.sum()
+}
+
+// repeatedly takes the heaviest remaining pair; ties go to the lowest column, then the lowest row
+pub fn greedy(weights: &[Vec<f32>]) -> Vec<Option<usize>> {
+	let num_rows = weights.len();
+	let num_cols = weights.first().map_or(0, |r| r.len());
+	let mut out = vec![None; num_rows];
+	let mut row_open = vec![true; num_rows];
+	let mut col_open = vec![true; num_cols];
+
+	for _ in 0..num_rows.min(num_cols) {
+		let mut best: Option<(usize, usize)> = None;
+		let mut best_weight = f32::NEG_INFINITY;
+		for col in (0..num_cols).filter(|&c| col_open[c]) {
+			for row in (0..num_rows).filter(|&r| row_open[r]) {
+				if best.is_none() || weights[row][col] > best_weight {
+					best_weight = weights[row][col];
+					best = Some((row, col));
+				}
+			}
+		}
+		let (row, col) = best.unwrap();
+		out[row] = Some(col);
+		row_open[row] = false;
+		col_open[col] = false;
+	}
+	out
+}
+
+// Kuhn-Munkres with row and column potentials, O(rows^2 * cols) for rows <= cols.
+// https://en.wikipedia.org/wiki/Hungarian_algorithm
+pub fn hungarian(weights: &[Vec<f32>]) -> Vec<Option<usize>> {
+	let num_rows = weights.len();
+	let num_cols = weights.first().map_or(0, |r| r.len());
+	if num_rows == 0 || num_cols == 0 {
+		return vec![None; num_rows];
+	}
+	if num_rows > num_cols {
+		// solve the transpose, then turn column -> row back into row -> column
+		let transposed: Vec<Vec<f32>> = (0..num_cols)
+			.map(|c| (0..num_rows).map(|r| weights[r][c]).collect())
+			.collect();
+		let mut out = vec![None; num_rows];
+		for (col, row) in hungarian(&transposed).into_iter().enumerate() {
+			if let Some(r) = row {
+				out[r] = Some(col);
+			}
+		}
+		return out;
+	}
+
+	// minimizes cost = -weight; index 0 is a dummy row/column, so everything is 1-based
+	let cost = |r: usize, c: usize| -(weights[r-1][c-1] as f64);
+	let mut u = vec![0.0; num_rows+1];
+	let mut v = vec![0.0; num_cols+1];
+	let mut row_of_col = vec![0; num_cols+1];
+	let mut way = vec![0; num_cols+1];
+	for r in 1..(num_rows+1) {
+		row_of_col[0] = r;
+		let mut col0 = 0;
+		let mut min_slack = vec![f64::INFINITY; num_cols+1];
+		let mut used = vec![false; num_cols+1];
+		loop {
+			used[col0] = true;
+			let row0 = row_of_col[col0];
+			let mut delta = f64::INFINITY;
+			let mut col1 = 0;
+			for c in 1..(num_cols+1) {
+				if !used[c] {
+					let slack = cost(row0, c) - u[row0] - v[c];
+					if slack < min_slack[c] {
+						min_slack[c] = slack;
+						way[c] = col0;
+					}
+					if min_slack[c] < delta {
+						delta = min_slack[c];
+						col1 = c;
+					}
+				}
+			}
+			for c in 0..(num_cols+1) {
+				if used[c] {
+					u[row_of_col[c]] += delta;
+					v[c] -= delta;
+				} else {
+					min_slack[c] -= delta;
+				}
+			}
+			col0 = col1;
+			if row_of_col[col0] == 0 {
+				break;
+			}
+		}
+		// flip the augmenting path
+		while col0 != 0 {
+			let col1 = way[col0];
+			row_of_col[col0] = row_of_col[col1];
+			col0 = col1;
+		}
+	}
+
+	let mut out = vec![None; num_rows];
+	for c in 1..(num_cols+1) {
+		if row_of_col[c] != 0 {
+			out[row_of_col[c]-1] = Some(c-1);
+		}
+	}
+	out
+}
+
+// Like hungarian, but the columns are grouped into clusters that can only keep so many
+// rows busy. Rows up to capacity[k] go into cluster k for free; past that the j-th row
+// (from 1) over capacity costs j*fill_cost[k], in the units of the weights, so a full
+// cluster pushes rows out to other clusters. Each column still goes to one row at most.
+//
+// source -> row (1) -> column (1) -> cluster -> sink, where the cluster has one edge
+// of capacity 1 per row it can take, at the cost of that row.
+pub fn min_cost_flow(weights: &[Vec<f32>], cluster_of_col: &[usize], capacity: &[usize],
+	fill_cost: &[f64]) -> Vec<Option<usize>> {
+	let num_rows = weights.len();
+	let num_cols = weights.first().map_or(0, |r| r.len());
+	let num_clusters = capacity.len();
+	let source = 0;
+	let row_node = |r: usize| 1 + r;
+	let col_node = |c: usize| 1 + num_rows + c;
+	let cluster_node = |k: usize| 1 + num_rows + num_cols + k;
+	let sink = 1 + num_rows + num_cols + num_clusters;
+
+	let mut flow = FlowGraph::new(sink + 1);
+	for r in 0..num_rows {
+		flow.add_edge(source, row_node(r), 1, 0.0);
+	}
+	let mut row_edges = vec![Vec::with_capacity(num_cols); num_rows];
+	for (r, row) in weights.iter().enumerate() {
+		for (c, &w) in row.iter().enumerate() {
+			row_edges[r].push(flow.add_edge(row_node(r), col_node(c), 1, -(w as f64)));
+		}
+	}
+	let mut cols_in_cluster = vec![0; num_clusters];
+	for (c, &k) in cluster_of_col.iter().enumerate() {
+		flow.add_edge(col_node(c), cluster_node(k), 1, 0.0);
+		cols_in_cluster[k] += 1;
+	}
+	for (k, &cap) in capacity.iter().enumerate() {
+		for j in 0..cols_in_cluster[k].min(num_rows) {
+			let over_capacity = (j + 1).saturating_sub(cap);
+			flow.add_edge(cluster_node(k), sink, 1, fill_cost[k]*over_capacity as f64);
+		}
+	}
+	flow.run(source, sink);
+
+	row_edges.iter()
+		.map(|edges| edges.iter().position(|&e| flow.edges[e].cap == 0))
+		.collect()
+}
+
+struct FlowEdge {
+	to: usize,
+	cap: i32,
+	cost: f64,
+}
+
+// residual graph; edge i and i^1 are each other's reverse
+struct FlowGraph {
+	edges: Vec<FlowEdge>,
+	adj: Vec<Vec<usize>>,
+}
+
+impl FlowGraph {
+	fn new(num_nodes: usize) -> FlowGraph {
+		FlowGraph { edges: Vec::new(), adj: vec![Vec::new(); num_nodes] }
+	}
+
+	fn add_edge(&mut self, from: usize, to: usize, cap: i32, cost: f64) -> usize {
+		let idx = self.edges.len();
+		self.edges.push(FlowEdge { to, cap, cost });
+		self.edges.push(FlowEdge { to: from, cap: 0, cost: -cost });
+		self.adj[from].push(idx);
+		self.adj[to].push(idx + 1);
+		idx
+	}
+
+	// successive shortest paths, one unit at a time, until the sink is unreachable;
+	// shortest paths are found with a queue-based Bellman-Ford since costs can be negative
+	fn run(&mut self, source: usize, sink: usize) {
+		let n = self.adj.len();
+		loop {
+			let mut dist = vec![f64::INFINITY; n];
+			let mut via = vec![usize::MAX; n];
+			let mut queued = vec![false; n];
+			let mut queue = VecDeque::new();
+			dist[source] = 0.0;
+			queue.push_back(source);
+			while let Some(u) = queue.pop_front() {
+				queued[u] = false;
+				for &e in &self.adj[u] {
+					let edge = &self.edges[e];
+					if edge.cap > 0 && dist[u] + edge.cost < dist[edge.to] - 1e-9 {
+						dist[edge.to] = dist[u] + edge.cost;
+						via[edge.to] = e;
+						if !queued[edge.to] {
+							queued[edge.to] = true;
+							queue.push_back(edge.to);
+						}
+					}
+				}
+			}
+			if via[sink] == usize::MAX {
+				break;
+			}
+			let mut v = sink;
+			while v != source {
+				let e = via[v];
+				self.edges[e].cap -= 1;
+				self.edges[e ^ 1].cap += 1;
+				v = self.edges[e ^ 1].to;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::{Rng, SeedableRng};
+	use rand::prng::XorShiftRng;
+
+	const MAX_SIZE: usize = 5;
+
+	// integer weights, so that totals compare exactly
+	fn random_weights(rng: &mut XorShiftRng, num_rows: usize, num_cols: usize) -> Vec<Vec<f32>> {
+		(0..num_rows).map(|_| (0..num_cols).map(|_| rng.gen_range(-50, 100) as f32).collect()).collect()
+	}
+
+	// every way to give min(rows, cols) rows a distinct column each
+	fn full_matchings(num_rows: usize, num_cols: usize) -> Vec<Vec<Option<usize>>> {
+		fn extend(row: usize, num_rows: usize, num_cols: usize, skips_left: usize, used: &mut Vec<bool>,
+			current: &mut Vec<Option<usize>>, out: &mut Vec<Vec<Option<usize>>>) {
+			if row == num_rows {
+				out.push(current.clone());
+				return;
+			}
+			if skips_left > 0 {
+				current.push(None);
+				extend(row + 1, num_rows, num_cols, skips_left - 1, used, current, out);
+				current.pop();
+			}
+			for c in 0..num_cols {
+				if !used[c] {
+					used[c] = true;
+					current.push(Some(c));
+					extend(row + 1, num_rows, num_cols, skips_left, used, current, out);
+					current.pop();
+					used[c] = false;
+				}
+			}
+		}
+		let mut out = Vec::new();
+		extend(0, num_rows, num_cols, num_rows.saturating_sub(num_cols), &mut vec![false; num_cols],
+			&mut Vec::new(), &mut out);
+		out
+	}
+
+	// a full matching: every column at most once and as many rows as possible matched
+	fn assert_full_matching(assignment: &[Option<usize>], num_rows: usize, num_cols: usize) {
+		assert_eq!(assignment.len(), num_rows);
+		let mut cols: Vec<usize> = assignment.iter().filter_map(|&c| c).collect();
+		assert_eq!(cols.len(), num_rows.min(num_cols), "{:?}", assignment);
+		assert!(cols.iter().all(|&c| c < num_cols), "{:?}", assignment);
+		cols.sort();
+		cols.dedup();
+		assert_eq!(cols.len(), num_rows.min(num_cols), "a column is used twice: {:?}", assignment);
+	}
+
+	#[test]
+	fn hungarian_matches_brute_force() {
+		let mut rng = XorShiftRng::seed_from_u64(12);
+		for num_rows in 0..(MAX_SIZE+1) {
+			for num_cols in 0..(MAX_SIZE+1) {
+				let matchings = full_matchings(num_rows, num_cols);
+				for _ in 0..20 {
+					let weights = random_weights(&mut rng, num_rows, num_cols);
+					let best = matchings.iter().map(|m| total_weight(&weights, m)).fold(f64::NEG_INFINITY, f64::max);
+					let optimal = hungarian(&weights);
+					if num_cols > 0 {
+						assert_full_matching(&optimal, num_rows, num_cols);
+						assert_eq!(total_weight(&weights, &optimal), best, "{:?}", weights);
+					} else {
+						assert_eq!(optimal, vec![None; num_rows]);
+					}
+				}
+			}
+		}
+	}
+
+	// total weight less what the rows over each cluster's capacity cost
+	fn flow_objective(weights: &[Vec<f32>], cluster_of_col: &[usize], capacity: &[usize], fill_cost: &[f64],
+		assignment: &[Option<usize>]) -> f64 {
+		let mut rows_in_cluster = vec![0; capacity.len()];
+		for &c in assignment.iter().flatten() {
+			rows_in_cluster[cluster_of_col[c]] += 1;
+		}
+		let over_cost: f64 = (0..capacity.len())
+			.map(|k| {
+				let over = rows_in_cluster[k] - rows_in_cluster[k].min(capacity[k]);
+				fill_cost[k]*(over*(over + 1)/2) as f64
+			})
+			.sum();
+		total_weight(weights, assignment) - over_cost
+	}
+
+	#[test]
+	fn min_cost_flow_matches_brute_force() {
+		let mut rng = XorShiftRng::seed_from_u64(13);
+		for num_rows in 1..(MAX_SIZE+1) {
+			for num_cols in 1..(MAX_SIZE+1) {
+				let matchings = full_matchings(num_rows, num_cols);
+				for _ in 0..20 {
+					let weights = random_weights(&mut rng, num_rows, num_cols);
+					let num_clusters = rng.gen_range(1, num_cols + 1);
+					let cluster_of_col: Vec<usize> = (0..num_cols).map(|c| if c < num_clusters { c } else {
+						rng.gen_range(0, num_clusters)
+					}).collect();
+					let capacity: Vec<usize> = (0..num_clusters).map(|_| rng.gen_range(1, 4)).collect();
+					let fill_cost: Vec<f64> = (0..num_clusters).map(|_| rng.gen_range(0, 40) as f64).collect();
+
+					let objective = |m: &[Option<usize>]| flow_objective(&weights, &cluster_of_col, &capacity, &fill_cost, m);
+					let best = matchings.iter().map(|m| objective(m)).fold(f64::NEG_INFINITY, f64::max);
+					let flow = min_cost_flow(&weights, &cluster_of_col, &capacity, &fill_cost);
+					assert_full_matching(&flow, num_rows, num_cols);
+					assert_eq!(objective(&flow), best,
+						"{:?}, clusters {:?}, capacity {:?}, fill cost {:?}", weights, cluster_of_col, capacity, fill_cost);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn min_cost_flow_without_fill_cost_is_a_best_matching() {
+		let mut rng = XorShiftRng::seed_from_u64(14);
+		for _ in 0..100 {
+			let weights = random_weights(&mut rng, 4, 6);
+			let flow = min_cost_flow(&weights, &[0, 0, 0, 1, 1, 1], &[1, 1], &[0.0, 0.0]);
+			assert_eq!(total_weight(&weights, &flow), total_weight(&weights, &hungarian(&weights)));
+		}
+	}
+
+	#[test]
+	fn fill_cost_spreads_rows_over_clusters() {
+		// both rows prefer the two columns of cluster 0, which only has room for one
+		let weights = vec![vec![10.0, 9.5, 9.0], vec![10.0, 9.5, 9.0]];
+		let clusters = [0, 0, 1];
+		let crowded = min_cost_flow(&weights, &clusters, &[1, 1], &[0.4, 0.4]);
+		let mut cols: Vec<usize> = crowded.iter().flatten().cloned().collect();
+		cols.sort();
+		assert_eq!(cols, vec![0, 1]); // the second row in cluster 0 costs less than the 0.5 it gains
+		let spread = min_cost_flow(&weights, &clusters, &[1, 1], &[0.6, 0.6]);
+		let mut cols: Vec<usize> = spread.iter().flatten().cloned().collect();
+		cols.sort();
+		assert_eq!(cols, vec![0, 2]);
+		// with room for both, nothing is pushed out
+		let roomy = min_cost_flow(&weights, &clusters, &[2, 1], &[100.0, 100.0]);
+		let mut cols: Vec<usize> = roomy.iter().flatten().cloned().collect();
+		cols.sort();
+		assert_eq!(cols, vec![0, 1]);
+	}
+}
@@ -0,0 +1,222 @@
+// Windowed hierarchical cooperative A* (WHCA*): ships are planned one after another
+// over (Point, turn), each one avoiding the cells the ships before it reserved.
+// https://www.aaai.org/Papers/AIIDE/2005/AIIDE05-020.pdf
+use std::collections::{BinaryHeap, BTreeMap, HashMap, HashSet};
+use std::cmp::{min, Ordering};
+use game::Game;
+use data::{Direction, Point};
+
+// a turn spent on the way counts as much as this much halite burned
+pub const TURN_COST: isize = 1000;
+
+// which ship holds each cell at each turn from now, turn 0 being the current positions
+pub struct ReservationTable {
+	pub window: usize,
+	reserved: HashMap<(Point, usize), usize>,
+	shared: HashSet<Point>, // cells any number of my ships may end up on, e.g. drop points in the endgame
+	paths: BTreeMap<usize, Vec<Point>>,
+}
+
+impl ReservationTable {
+	pub fn new(window: usize) -> ReservationTable {
+		ReservationTable {
+			window,
+			reserved: HashMap::new(),
+			shared: HashSet::new(),
+			paths: BTreeMap::new(),
+		}
+	}
+
+	pub fn share(&mut self, pos: Point) {
+		self.shared.insert(pos);
+	}
+
+	pub fn owner(&self, pos: Point, turn: usize) -> Option<usize> {
+		if self.shared.contains(&pos) {
+			None
+		} else {
+			self.reserved.get(&(pos, turn)).cloned()
+		}
+	}
+
+	pub fn is_free(&self, pos: Point, turn: usize) -> bool {
+		self.owner(pos, turn).is_none()
+	}
+
+	// path[t] is where the ship is t turns from now; with wait_at_end the ship is taken
+	// to stay on the last cell for the rest of the window
+	pub fn reserve_path(&mut self, id: usize, path: Vec<Point>, wait_at_end: bool) {
+		let turns = if wait_at_end { self.window+1 } else { path.len() };
+		for t in 0..turns {
+			let pos = path[min(t, path.len()-1)];
+			if !self.shared.contains(&pos) {
+				self.reserved.insert((pos, t), id);
+			}
+		}
+		self.paths.insert(id, path);
+	}
+
+	pub fn paths(&self) -> &BTreeMap<usize, Vec<Point>> {
+		&self.paths
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct NodePriority {
+	idx: usize,
+	w: isize,
+}
+
+impl Ord for NodePriority {
+	fn cmp(&self, other: &NodePriority) -> Ordering {
+		other.w.cmp(&self.w).then(other.idx.cmp(&self.idx)) // smaller numbers first, then older nodes
+	}
+}
+
+impl PartialOrd for NodePriority {
+	fn partial_cmp(&self, other: &NodePriority) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+struct Node {
+	pos: Point,
+	turn: usize,
+	cargo: usize,
+	cost: isize,
+	parent: Option<usize>,
+}
+
+// Cheapest path from start toward goal through the free cells of the table, at most
+// table.window turns long. A ship carrying less than the move cost of its tile can only
+// stay, and staying mines the tile. step_cost(pos, turn) is added for being on pos
+// after turn moves. The search stops early once the ship reaches goal, and if
+// stay_at_goal, can also sit on it for the rest of the window. Returns None if the ship
+// cannot even stay for one turn.
+pub fn plan_path(game: &Game, table: &ReservationTable, start: Point, cargo: usize, goal: Point,
+	stay_at_goal: bool, step_cost: &dyn Fn(Point, usize) -> isize) -> Option<Vec<Point>> {
+	let window = table.window;
+	let mut nodes = vec![Node { pos: start, turn: 0, cargo, cost: 0, parent: None }];
+	let mut frontier = BinaryHeap::new();
+	frontier.push(NodePriority { idx: 0, w: TURN_COST*game.dist(start, goal) as isize });
+	// the most cargo any expanded node at (pos, turn) had; nodes at the same cell come off the
+	// frontier cheapest first, so a later one is only worth expanding if it carries more
+	let mut closed: HashMap<(Point, usize), usize> = HashMap::new();
+	let dominated = |closed: &HashMap<(Point, usize), usize>, pos: Point, turn: usize, cargo: usize|
+		closed.get(&(pos, turn)).is_some_and(|&c| c >= cargo);
+
+	while let Some(NodePriority { idx, w: _ }) = frontier.pop() {
+		let (pos, turn, cargo, cost) = (nodes[idx].pos, nodes[idx].turn, nodes[idx].cargo, nodes[idx].cost);
+		if dominated(&closed, pos, turn, cargo) {
+			continue; // an earlier copy, no more expensive, carried at least as much
+		}
+		closed.insert((pos, turn), cargo);
+		let done = pos == goal && (!stay_at_goal || (turn..(window+1)).all(|t| table.is_free(pos, t)));
+		if turn == window || (done && turn > 0) {
+			let mut path = Vec::new();
+			let mut node = Some(idx);
+			while let Some(i) = node {
+				path.push(nodes[i].pos);
+				node = nodes[i].parent;
+			}
+			path.reverse();
+			return Some(path);
+		}
+
+		let inspired = *game.inspired.get(pos);
+		let move_cost = game.constants.move_cost(game.halite_at(pos), inspired);
+		for d in Direction::all_directions() {
+			if d != Direction::Still && cargo < move_cost {
+				continue;
+			}
+			let next = game.step_toward(pos, d);
+			if !table.is_free(next, turn+1) {
+				continue;
+			}
+			let (next_cargo, halite_cost) = if d == Direction::Still {
+				// as the engine mines: the bonus only fills what room the extracted halite left
+				let space = game.constants.max_halite.saturating_sub(cargo);
+				let extracted = min(game.constants.extracted(game.halite_at(pos), inspired), space);
+				let bonus = if inspired { min(game.constants.inspiration_bonus(extracted), space - extracted) } else { 0 };
+				(cargo + extracted + bonus, 0)
+			} else {
+				(cargo - move_cost, move_cost as isize)
+			};
+			if dominated(&closed, next, turn+1, next_cargo) {
+				continue;
+			}
+			let next_cost = cost + TURN_COST + halite_cost + step_cost(next, turn+1);
+			nodes.push(Node { pos: next, turn: turn+1, cargo: next_cargo, cost: next_cost, parent: Some(idx) });
+			frontier.push(NodePriority {
+				idx: nodes.len()-1,
+				w: next_cost + TURN_COST*game.dist(next, goal) as isize,
+			});
+		}
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use engine::Engine;
+
+	fn at(x: isize, y: isize) -> Point {
+		Point { x, y }
+	}
+
+	// an empty 8x8 map, apart from the given tiles
+	fn game_with_halite(tiles: &[(Point, usize)]) -> Game {
+		let mut engine = Engine::flat(1, 8, 8, 0);
+		for &(pos, halite) in tiles {
+			*engine.halite_map.get_mut(pos) = halite;
+		}
+		engine.game_view(0)
+	}
+
+	fn no_step_cost(_: Point, _: usize) -> isize {
+		0
+	}
+
+	// every step of the path is free in the table and at most one tile long
+	fn assert_fits(game: &Game, table: &ReservationTable, path: &[Point]) {
+		for t in 1..path.len() {
+			assert!(table.is_free(path[t], t), "{:?} is taken at turn {}", path[t], t);
+			assert!(game.dist(path[t-1], path[t]) <= 1, "{:?} jumps at turn {}", path, t);
+		}
+	}
+
+	#[test]
+	fn plan_path_waits_for_a_reserved_cell() {
+		let game = game_with_halite(&[]);
+		let mut table = ReservationTable::new(4);
+		table.reserve_path(9, vec![at(1, 0), at(1, 0)], false); // in the way at turns 0 and 1 only
+		let path = plan_path(&game, &table, at(0, 0), 0, at(2, 0), false, &no_step_cost).unwrap();
+		assert_fits(&game, &table, &path);
+		assert_eq!(path, vec![at(0, 0), at(0, 0), at(1, 0), at(2, 0)]);
+	}
+
+	#[test]
+	fn plan_path_only_stops_on_a_goal_it_can_keep() {
+		let game = game_with_halite(&[]);
+		let mut table = ReservationTable::new(4);
+		table.reserve_path(9, vec![at(5, 5), at(5, 5), at(5, 5), at(1, 0)], false); // takes the goal at turn 3
+		let path = plan_path(&game, &table, at(0, 0), 0, at(1, 0), true, &no_step_cost).unwrap();
+		assert_fits(&game, &table, &path);
+		assert_eq!(path.len(), table.window + 1);
+		assert!(path[3] != at(1, 0));
+		assert_eq!(path[4], at(1, 0));
+	}
+
+	#[test]
+	fn plan_path_expands_a_costlier_arrival_that_carries_more() {
+		// Leaving (1, 0) costs 10 halite, so an empty ship has to mine there first. Waiting
+		// at the start reaches (1, 0) at turn 2 cheapest, but empty; arriving at turn 1
+		// (made to cost 500) and mining gets there at turn 2 with 25 and can go on at once.
+		let game = game_with_halite(&[(at(1, 0), 100)]);
+		let table = ReservationTable::new(6);
+		let step_cost = |pos: Point, turn: usize| if pos == at(1, 0) && turn == 1 { 500 } else { 0 };
+		let path = plan_path(&game, &table, at(0, 0), 0, at(3, 0), false, &step_cost).unwrap();
+		assert_eq!(path, vec![at(0, 0), at(1, 0), at(1, 0), at(2, 0), at(3, 0)]);
+	}
+}
@@ -1,4 +1,6 @@
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use serde_json::Value;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Point {
@@ -6,6 +8,19 @@ pub struct Point {
     pub y: isize,
 }
 
+impl Point {
+    pub fn to_json(&self) -> Value {
+        json!([self.x, self.y])
+    }
+
+    pub fn from_json(v: &Value) -> Result<Point, String> {
+        match (v[0].as_i64(), v[1].as_i64()) {
+            (Some(x), Some(y)) => Ok(Point { x: x as isize, y: y as isize }),
+            _ => Err(format!("expected [x, y], got {}", v)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Target {
     pub pos: Point,
@@ -60,6 +75,17 @@ impl Direction {
         }
     }
 
+    pub fn from_char_encoding(c: char) -> Option<Direction> {
+        match c {
+            'n' => Some(Direction::North),
+            'e' => Some(Direction::East),
+            's' => Some(Direction::South),
+            'w' => Some(Direction::West),
+            'o' => Some(Direction::Still),
+            _ => None,
+        }
+    }
+
     pub fn all_directions() -> Vec<Direction> {
         vec![Direction::North, Direction::East, 
         Direction::South, Direction::West, Direction::Still]
@@ -71,8 +97,11 @@ impl Direction {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ShipCommand {
     MakeDropoff(),
     MoveShip(Direction),
-}
\ No newline at end of file
+}
+
+// whether to spawn a ship, and a command for each ship by id
+pub type TurnCommands = (bool, HashMap<usize, ShipCommand>);
\ No newline at end of file
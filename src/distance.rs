@@ -0,0 +1,241 @@
+use std::collections::BinaryHeap;
+use std::cmp::Ordering;
+use game::{Game, GMap};
+use data::{Direction, Point};
+use cooperative::TURN_COST;
+
+// nearest/dist of a tile that no source reaches
+pub const UNREACHED: usize = usize::MAX;
+
+// For every tile, the closest of a set of sources and how far it is. Distances add up
+// edge_cost(from, to) over the steps leading away from the source, so a field of
+// "cost to get home" should charge what moving from `to` back to `from` costs. Ties go
+// to the source that was given first.
+#[derive(Debug, Clone)]
+pub struct DistanceField {
+    pub nearest: GMap<usize>, // index into sources
+    pub dist: GMap<usize>,
+    pub sources: Vec<Point>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FieldEntry {
+    dist: usize,
+    source: usize,
+    idx: usize,
+}
+
+impl Ord for FieldEntry {
+    fn cmp(&self, other: &FieldEntry) -> Ordering {
+        // so that smaller distances, then earlier sources go first
+        (other.dist, other.source, other.idx).cmp(&(self.dist, self.source, self.idx))
+    }
+}
+
+impl PartialOrd for FieldEntry {
+    fn partial_cmp(&self, other: &FieldEntry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// every step costs 1, so dist is the number of moves
+pub fn unit_cost(_: Point, _: Point) -> usize {
+    1
+}
+
+impl DistanceField {
+    pub fn new(width: usize, height: usize, sources: &[Point], edge_cost: &dyn Fn(Point, Point) -> usize) -> DistanceField {
+        let mut field = DistanceField {
+            nearest: GMap::new(width, height, UNREACHED),
+            dist: GMap::new(width, height, UNREACHED),
+            sources: Vec::new(),
+        };
+        let seeds: Vec<usize> = (0..sources.len()).collect();
+        field.sources = sources.to_vec();
+        field.grow(&seeds, edge_cost);
+        field
+    }
+
+    // Only the tiles that end up closer to the new source are visited, so this is much
+    // cheaper than building the field again. edge_cost must be the one the field was built with.
+    pub fn add_source(&mut self, pos: Point, edge_cost: &dyn Fn(Point, Point) -> usize) {
+        self.sources.push(pos);
+        let source = self.sources.len()-1;
+        self.grow(&[source], edge_cost);
+    }
+
+    // the field as it would be with one more source, e.g. a dropoff we might build
+    pub fn with_source(&self, pos: Point, edge_cost: &dyn Fn(Point, Point) -> usize) -> DistanceField {
+        let mut field = self.clone();
+        field.add_source(pos, edge_cost);
+        field
+    }
+
+    pub fn nearest_source(&self, pos: Point) -> Option<Point> {
+        match *self.nearest.get(pos) {
+            UNREACHED => None,
+            source => Some(self.sources[source]),
+        }
+    }
+
+    pub fn dist(&self, pos: Point) -> usize {
+        *self.dist.get(pos)
+    }
+
+    fn label(&self, pos: Point) -> (usize, usize) {
+        (*self.dist.get(pos), *self.nearest.get(pos))
+    }
+
+    fn set_label(&mut self, pos: Point, (dist, source): (usize, usize)) {
+        *self.dist.get_mut(pos) = dist;
+        *self.nearest.get_mut(pos) = source;
+    }
+
+    fn pos(&self, idx: usize) -> Point {
+        Point { x: (idx % self.dist.width) as isize, y: (idx / self.dist.width) as isize }
+    }
+
+    // Dijkstra from the given sources at once, on (dist, source) so that ties keep the
+    // earlier source; a tile is only taken over if that beats what it already has
+    fn grow(&mut self, seeds: &[usize], edge_cost: &dyn Fn(Point, Point) -> usize) {
+        let width = self.dist.width;
+        let height = self.dist.height;
+        let mut frontier = BinaryHeap::new();
+        for &source in seeds {
+            let pos = self.sources[source];
+            let pos = Point { x: pos.x.rem_euclid(width as isize), y: pos.y.rem_euclid(height as isize) };
+            if (0, source) < self.label(pos) {
+                self.set_label(pos, (0, source));
+                frontier.push(FieldEntry { dist: 0, source, idx: pos.y as usize*width + pos.x as usize });
+            }
+        }
+
+        while let Some(FieldEntry { dist, source, idx }) = frontier.pop() {
+            let current = self.pos(idx);
+            if (dist, source) > self.label(current) {
+                continue; // stale entry, the tile was claimed more cheaply since
+            }
+            for &(dx, dy) in &[(0, -1), (0, 1), (1, 0), (-1, 0)] {
+                let next = Point {
+                    x: (current.x + dx).rem_euclid(width as isize),
+                    y: (current.y + dy).rem_euclid(height as isize),
+                };
+                let next_dist = dist.saturating_add(edge_cost(current, next));
+                if (next_dist, source) < self.label(next) {
+                    self.set_label(next, (next_dist, source));
+                    frontier.push(FieldEntry { dist: next_dist, source, idx: next.y as usize*width + next.x as usize });
+                }
+            }
+        }
+    }
+}
+
+// What carrying halite home costs from every tile, by way of the cheapest route to any of
+// my drop points, where every turn on the way counts as TURN_COST halite on top of what
+// the moves burn. Rebuilt every turn since moving costs change with the halite.
+#[derive(Debug, Clone)]
+pub struct HomeField {
+    pub cost: DistanceField, // TURN_COST*turns + halite burned
+    pub turns: GMap<usize>,
+    pub halite: GMap<usize>, // burned on the way, leaving the starting tile included
+    pub first_step: GMap<Direction>, // Still on the drop points and where no route goes
+}
+
+impl HomeField {
+    // for before the drop points are known
+    pub fn empty(width: usize, height: usize) -> HomeField {
+        HomeField {
+            cost: DistanceField::new(width, height, &[], &unit_cost),
+            turns: GMap::new(width, height, UNREACHED),
+            halite: GMap::new(width, height, UNREACHED),
+            first_step: GMap::new(width, height, Direction::Still),
+        }
+    }
+
+    pub fn new(game: &Game) -> HomeField {
+        // grows outward from the drop points, so the step from `from` to `to` is paid for
+        // by a ship leaving `to`
+        let move_cost = |pos: Point| game.constants.move_cost(game.halite_at(pos), *game.inspired.get(pos));
+        let edge_cost = |_: Point, to: Point| TURN_COST as usize + move_cost(to);
+        let mut field = HomeField::empty(game.width, game.height);
+        field.cost = DistanceField::new(game.width, game.height, &game.my_drop_pts, &edge_cost);
+
+        // walk the tiles outward, each one following the neighbor its cost came from
+        let mut order: Vec<(usize, Point)> = field.cost.dist.points()
+            .filter(|&(_, &d)| d != UNREACHED)
+            .map(|(p, &d)| (d, p))
+            .collect();
+        order.sort_by_key(|&(d, p)| (d, p.y, p.x));
+        for (d, pos) in order {
+            if d == 0 {
+                *field.turns.get_mut(pos) = 0;
+                *field.halite.get_mut(pos) = 0;
+                continue;
+            }
+            for dir in Direction::adjacent_directions() {
+                let next = game.step_toward(pos, dir);
+                if field.cost.dist(next).saturating_add(edge_cost(next, pos)) == d {
+                    *field.turns.get_mut(pos) = *field.turns.get(next) + 1;
+                    *field.halite.get_mut(pos) = *field.halite.get(next) + move_cost(pos);
+                    *field.first_step.get_mut(pos) = dir;
+                    break;
+                }
+            }
+        }
+        field
+    }
+
+    // the drop point the cheapest route ends at
+    pub fn home(&self, pos: Point) -> Option<Point> {
+        self.cost.nearest_source(pos)
+    }
+
+    pub fn turns(&self, pos: Point) -> usize {
+        *self.turns.get(pos)
+    }
+
+    pub fn halite(&self, pos: Point) -> usize {
+        *self.halite.get(pos)
+    }
+
+    // where the cheapest route home from pos goes first
+    pub fn first_step(&self, pos: Point) -> Direction {
+        *self.first_step.get(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::Engine;
+    use rand::{Rng, SeedableRng};
+    use rand::prng::XorShiftRng;
+
+    #[test]
+    fn home_field_first_step_follows_the_cheapest_route() {
+        let mut rng = XorShiftRng::seed_from_u64(19);
+        for &(width, height) in &[(1, 1), (2, 3), (5, 5), (8, 6)] {
+            let mut engine = Engine::flat(2, width, height, 0);
+            for y in 0..height {
+                for x in 0..width {
+                    *engine.halite_map.get_mut(Point { x: x as isize, y: y as isize }) = rng.gen_range(0, 1000);
+                }
+            }
+            let game = engine.game_view(0);
+            let home = HomeField::new(&game);
+            for (pos, &cost) in home.cost.dist.points() {
+                let dir = home.first_step(pos);
+                if game.my_drop_pts.contains(&pos) {
+                    assert_eq!((cost, dir), (0, Direction::Still));
+                    continue;
+                }
+                assert!(dir != Direction::Still, "{}x{} at {:?}", width, height, pos);
+                let next = game.step_toward(pos, dir);
+                let move_cost = game.constants.move_cost(game.halite_at(pos), false);
+                assert_eq!(cost, TURN_COST as usize + move_cost + home.cost.dist(next), "{}x{} at {:?}", width, height, pos);
+                assert_eq!(home.turns(pos), home.turns(next) + 1);
+                assert_eq!(home.halite(pos), home.halite(next) + move_cost);
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::fs;
+use serde_json::{Map, Value};
+use game::Game;
+use data::{Direction, Point, Ship, ShipCommand};
+use replay::Replay;
+
+// What a ship's next move depends on, one row of features per direction in the order of
+// Direction::all_directions(). "own" means the player of the ship, not necessarily me.
+pub const FEATURE_NAMES: &[&str] = &[
+    "still",             // 1 for staying
+    "still_cargo",       // cargo/max_halite when staying
+    "still_halite",      // halite of the tile/max_halite when staying
+    "dest_halite",       // halite of the tile moved to/max_halite
+    "toward_home",       // +1 if the move gets closer to the nearest own drop point, -1 if farther
+    "toward_home_cargo", // toward_home*cargo/max_halite
+    "toward_home_full",  // toward_home if the cargo is at least 90% full
+    "dest_own_ship",     // an own ship is on the tile moved to
+    "dest_enemy_ship",   // another player's ship is on the tile moved to
+    "enemies_near_dest", // other players' ships next to where the ship ends up, /4
+    "dest_own_drop",     // the ship ends up on an own drop point
+];
+
+// Softmax over the five moves of a ship, with a score linear in FEATURE_NAMES. Fitted
+// offline from replays by the enemy_model tool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnemyMoveModel {
+    pub weights: Vec<f64>,
+}
+
+// one ship on one turn of a replay
+pub struct MoveExample {
+    pub features: Vec<Vec<f64>>,
+    pub label: usize, // index into Direction::all_directions()
+}
+
+pub fn features(game: &Game, ship: &Ship) -> Vec<Vec<f64>> {
+    let max_halite = game.constants.max_halite as f64;
+    let cargo = ship.halite as f64/max_halite;
+    let home_pts = game.drop_pts_of(ship.player);
+    let home_dist = |pos: Point| home_pts.iter().map(|&d| game.dist(pos, d)).min().unwrap_or(0) as f64;
+    let here_halite = game.halite_at(ship.pos) as f64/max_halite;
+    let owner_at = |pos: Point| game.ship_map.get(&pos).map(|id| game.ships[id].player);
+
+    Direction::all_directions().into_iter().map(|d| {
+        let dest = game.step_toward(ship.pos, d);
+        let still = if d == Direction::Still { 1.0 } else { 0.0 };
+        let moving = 1.0 - still;
+        let toward_home = moving*(home_dist(ship.pos) - home_dist(dest));
+        let (dest_own, dest_enemy) = match owner_at(dest) {
+            Some(p) if d != Direction::Still => if p == ship.player { (1.0, 0.0) } else { (0.0, 1.0) },
+            _ => (0.0, 0.0),
+        };
+        let enemies_near = game.neighbors(dest).into_iter()
+            .filter(|&p| owner_at(p).is_some_and(|o| o != ship.player))
+            .count() as f64/4.0;
+        vec![
+            still,
+            still*cargo,
+            still*here_halite,
+            moving*game.halite_at(dest) as f64/max_halite,
+            toward_home,
+            toward_home*cargo,
+            if ship.halite as f64 >= 0.9*max_halite { toward_home } else { 0.0 },
+            dest_own,
+            dest_enemy,
+            enemies_near,
+            if home_pts.contains(&dest) { 1.0 } else { 0.0 },
+        ]
+    }).collect()
+}
+
+// a ship that cannot pay for moving off its tile has to stay
+pub fn must_stay(game: &Game, ship: &Ship) -> bool {
+    game.move_cost_of(ship) as isize > ship.halite
+}
+
+// every ship on every turn of the replay that could choose its move, seen from player 0
+pub fn replay_examples(replay: &Replay) -> Vec<MoveExample> {
+    let mut examples = Vec::new();
+    for (turn, frame) in replay.frames.iter().enumerate() {
+        let game = replay.game_at(turn, 0);
+        for id in frame.ships.keys() {
+            let ship = &game.ships[id];
+            let made = match frame.moves[ship.player].1.get(&ship.ship_id) {
+                Some(&ShipCommand::MoveShip(d)) => d,
+                Some(&ShipCommand::MakeDropoff()) => continue,
+                None => Direction::Still,
+            };
+            if must_stay(&game, ship) {
+                continue;
+            }
+            let label = Direction::all_directions().iter().position(|&d| d == made).unwrap();
+            examples.push(MoveExample { features: features(&game, ship), label });
+        }
+    }
+    examples
+}
+
+fn softmax(scores: &[f64]) -> Vec<f64> {
+    let top = scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let exps: Vec<f64> = scores.iter().map(|s| (s - top).exp()).collect();
+    let total: f64 = exps.iter().sum();
+    exps.iter().map(|e| e/total).collect()
+}
+
+impl EnemyMoveModel {
+    pub fn probabilities(&self, features: &[Vec<f64>]) -> Vec<f64> {
+        let scores: Vec<f64> = features.iter()
+            .map(|f| f.iter().zip(&self.weights).map(|(x, w)| x*w).sum())
+            .collect();
+        softmax(&scores)
+    }
+
+    // same shape as the hand-tuned prediction: each tile of the neighborhood with its chance
+    pub fn predict(&self, game: &Game, ship: &Ship) -> Vec<(Point, f64)> {
+        let probs = if must_stay(game, ship) {
+            vec![0.0, 0.0, 0.0, 0.0, 1.0]
+        } else {
+            self.probabilities(&features(game, ship))
+        };
+        game.neighborhood(ship.pos).into_iter().zip(probs).collect()
+    }
+
+    // mean negative log-likelihood of the moves that were made
+    pub fn log_loss(&self, examples: &[MoveExample]) -> f64 {
+        let total: f64 = examples.iter()
+            .map(|e| -self.probabilities(&e.features)[e.label].max(1e-12).ln())
+            .sum();
+        total/examples.len().max(1) as f64
+    }
+
+    // Newton's method on the L2-regularized log-likelihood, halving the step whenever
+    // it would make the loss worse
+    pub fn fit(examples: &[MoveExample], l2: f64, iterations: usize) -> EnemyMoveModel {
+        let n = FEATURE_NAMES.len();
+        let mut model = EnemyMoveModel { weights: vec![0.0; n] };
+        let objective = |m: &EnemyMoveModel| {
+            m.log_loss(examples) + 0.5*l2*m.weights.iter().map(|w| w*w).sum::<f64>()
+        };
+        let mut loss = objective(&model);
+        for _ in 0..iterations {
+            let mut gradient: Vec<f64> = model.weights.iter().map(|w| l2*w).collect();
+            let mut hessian = vec![vec![0.0; n]; n];
+            for (i, row) in hessian.iter_mut().enumerate() {
+                row[i] = l2;
+            }
+            let scale = 1.0/examples.len().max(1) as f64;
+            for e in examples {
+                let probs = model.probabilities(&e.features);
+                let mean: Vec<f64> = (0..n)
+                    .map(|k| e.features.iter().zip(&probs).map(|(f, p)| p*f[k]).sum())
+                    .collect();
+                for (d, f) in e.features.iter().enumerate() {
+                    let target = if d == e.label { 1.0 } else { 0.0 };
+                    for a in 0..n {
+                        gradient[a] += scale*(probs[d] - target)*f[a];
+                        let da = f[a] - mean[a];
+                        for b in 0..n {
+                            hessian[a][b] += scale*probs[d]*da*(f[b] - mean[b]);
+                        }
+                    }
+                }
+            }
+
+            let step = solve(hessian, gradient);
+            let mut t = 1.0;
+            loop {
+                let candidate = EnemyMoveModel {
+                    weights: model.weights.iter().zip(&step).map(|(w, s)| w - t*s).collect(),
+                };
+                let candidate_loss = objective(&candidate);
+                if candidate_loss <= loss {
+                    let improvement = loss - candidate_loss;
+                    model = candidate;
+                    loss = candidate_loss;
+                    if improvement < 1e-9 {
+                        return model;
+                    }
+                    break;
+                }
+                t *= 0.5;
+                if t < 1e-4 {
+                    return model;
+                }
+            }
+        }
+        model
+    }
+
+    pub fn to_json(&self) -> Value {
+        let weights: Map<String, Value> = FEATURE_NAMES.iter()
+            .zip(&self.weights)
+            .map(|(name, &w)| (name.to_string(), json!(w)))
+            .collect();
+        Value::Object(weights)
+    }
+
+    // a feature missing from the file gets weight 0
+    pub fn from_json(v: &Value) -> Result<EnemyMoveModel, String> {
+        let table = v.as_object().ok_or("enemy model weights should be an object")?;
+        for name in table.keys() {
+            if !FEATURE_NAMES.contains(&name.as_str()) {
+                return Err(format!("unknown enemy model feature {:?}", name));
+            }
+        }
+        let weights = FEATURE_NAMES.iter()
+            .map(|name| match table.get(*name) {
+                Some(w) => w.as_f64().ok_or_else(|| format!("weight of {} is not a number", name)),
+                None => Ok(0.0),
+            })
+            .collect::<Result<Vec<f64>, String>>()?;
+        Ok(EnemyMoveModel { weights })
+    }
+
+    pub fn load(path: &str) -> Result<EnemyMoveModel, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let v: Value = ::serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        EnemyMoveModel::from_json(&v).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = ::serde_json::to_string_pretty(&self.to_json()).map_err(|e| e.to_string())?;
+        fs::write(path, text + "\n").map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+// a x = b by Gaussian elimination with partial pivoting; a is symmetric positive definite here
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Vec<f64> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().partial_cmp(&a[j][col].abs()).unwrap()).unwrap();
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in (col+1)..n {
+            let factor = a[row][col]/pivot_row[col];
+            for (x, p) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor*p;
+            }
+            b[row] -= factor*b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let rest: f64 = ((row+1)..n).map(|k| a[row][k]*x[k]).sum();
+        x[row] = (b[row] - rest)/a[row][row];
+    }
+    x
+}
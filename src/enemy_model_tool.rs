@@ -0,0 +1,74 @@
+extern crate my_bot;
+
+use std::env;
+use std::process;
+
+use my_bot::Replay;
+use my_bot::enemy_model::{self, EnemyMoveModel, MoveExample, FEATURE_NAMES};
+
+const L2: f64 = 1e-3;
+const ITERATIONS: usize = 50;
+
+fn usage() -> ! {
+    eprintln!("usage: enemy_model train WEIGHTS REPLAY...   fit a model and write its weights\n\
+        \x20      enemy_model eval WEIGHTS REPLAY...    log-loss of the model on replays it was not fitted on");
+    process::exit(1);
+}
+
+fn load_examples(paths: &[String]) -> Vec<MoveExample> {
+    let mut examples = Vec::new();
+    for path in paths {
+        let replay = Replay::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        let before = examples.len();
+        examples.extend(enemy_model::replay_examples(&replay));
+        eprintln!("{}: {} turns, {} moves", path, replay.num_turns(), examples.len() - before);
+    }
+    examples
+}
+
+// Fits the enemy move model on the moves of every ship in a set of replays, or scores a
+// fitted one. Uniform is what guessing every possible move equally likely would score.
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 3 {
+        usage();
+    }
+    let (command, weights_path, replays) = (&args[0], &args[1], &args[2..]);
+    let examples = load_examples(replays);
+    if examples.is_empty() {
+        eprintln!("no moves to learn from");
+        process::exit(1);
+    }
+
+    let model = match command.as_str() {
+        "train" => {
+            let model = EnemyMoveModel::fit(&examples, L2, ITERATIONS);
+            for (name, w) in FEATURE_NAMES.iter().zip(&model.weights) {
+                println!("{:>18} {:>8.3}", name, w);
+            }
+            model.save(weights_path).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            });
+            model
+        },
+        "eval" => EnemyMoveModel::load(weights_path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        }),
+        _ => usage(),
+    };
+
+    let correct = examples.iter()
+        .filter(|e| {
+            let probs = model.probabilities(&e.features);
+            (0..probs.len()).all(|d| probs[d] <= probs[e.label])
+        })
+        .count();
+    println!("moves: {}", examples.len());
+    println!("log-loss: {:.4} (uniform {:.4})", model.log_loss(&examples), (5.0f64).ln());
+    println!("most likely move was made: {:.1}%", 100.0*correct as f64/examples.len() as f64);
+}
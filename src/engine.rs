@@ -0,0 +1,405 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::cmp::min;
+use rand::{Rng, SeedableRng};
+use rand::prng::XorShiftRng;
+use data::*;
+use game::{Game, GMap, Constants, LineReader, parse_line_of_nums};
+
+// something that happened during the last step, as recorded in replays
+#[derive(Debug, Clone)]
+pub enum Event {
+    Spawn { player: usize, ship_id: usize, pos: Point },
+    Construct { player: usize, dropoff_id: usize, pos: Point },
+    Shipwreck { pos: Point, ships: Vec<usize> },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PlayerStats {
+    pub ships_built: usize,
+    pub dropoffs_built: usize,
+    pub collisions: usize, // own ships destroyed in collisions
+    pub halite_deposited: usize,
+}
+
+// Offline stand-in for the official Halite III engine. Every player sees the
+// match through the same protocol lines the real engine would print, so a
+// `Game` built from `init_lines`/`frame_lines` is identical to one read from stdin.
+pub struct Engine {
+    pub constants: Constants,
+    pub num_players: usize,
+    pub width: usize,
+    pub height: usize,
+    pub turn_number: usize,
+    pub halite_map: GMap<usize>,
+    pub factories: Vec<Factory>,
+    pub dropoffs: Vec<Dropoff>,
+    pub ships: BTreeMap<usize, Ship>,
+    pub energy: Vec<usize>,
+    pub stats: Vec<PlayerStats>,
+    pub seed: u64,
+    next_ship_id: usize,
+    changed_cells: Vec<Point>,
+    events: Vec<Event>,
+    inspired: HashSet<usize>, // as of the end of the last turn
+    rng: XorShiftRng,
+}
+
+impl Engine {
+    pub fn new(constants: Constants, factories: Vec<Factory>, halite_map: GMap<usize>, seed: u64) -> Engine {
+        let num_players = factories.len();
+        let height = halite_map.height;
+        let width = halite_map.width;
+        Engine {
+            energy: vec![constants.initial_energy; num_players],
+            stats: vec![PlayerStats::default(); num_players],
+            constants,
+            num_players,
+            width, height,
+            turn_number: 0,
+            halite_map,
+            factories,
+            dropoffs: Vec::new(),
+            ships: BTreeMap::new(),
+            seed,
+            next_ship_id: 0,
+            changed_cells: Vec::new(),
+            events: Vec::new(),
+            inspired: HashSet::new(),
+            rng: XorShiftRng::seed_from_u64(seed),
+        }
+    }
+
+    // pick up a match from an arbitrary frame seen by the bot
+    pub fn from_game(game: &Game, seed: u64) -> Engine {
+        let mut engine = Engine::new(game.constants.clone(), game.factories.to_vec(),
+            game.halite_map.clone(), seed);
+        engine.turn_number = game.turn_number;
+        engine.energy = game.energy.to_vec();
+        engine.dropoffs = game.dropoffs.to_vec();
+        for (&id, ship) in &game.ships {
+            engine.ships.insert(id, ship.clone());
+        }
+        engine.next_ship_id = game.ships.keys().map(|&id| id + 1).max().unwrap_or(0);
+        engine
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.turn_number >= self.constants.max_turns
+    }
+
+    pub fn init_lines(&self, pid: usize) -> Vec<String> {
+        let mut lines = vec![self.constants.to_json().to_string(),
+            format!("{} {}", self.num_players, pid)];
+        for f in &self.factories {
+            lines.push(format!("{} {} {}", f.player, f.pos.x, f.pos.y));
+        }
+        lines.push(format!("{} {}", self.width, self.height));
+        for row in self.halite_map.rows() {
+            lines.push(row.iter().map(|h| h.to_string()).collect::<Vec<String>>().join(" "));
+        }
+        lines
+    }
+
+    // the frame for the upcoming turn, with the map cells changed by the last step
+    pub fn frame_lines(&self) -> Vec<String> {
+        let mut lines = vec![(self.turn_number + 1).to_string()];
+        for pid in 0..self.num_players {
+            let ships: Vec<&Ship> = self.ships.values().filter(|s| s.player == pid).collect();
+            let dropoffs: Vec<(usize, &Dropoff)> = self.dropoffs.iter().enumerate()
+                .filter(|(_, d)| d.player == pid)
+                .collect();
+            lines.push(format!("{} {} {} {}", pid, ships.len(), dropoffs.len(), self.energy[pid]));
+            for s in ships {
+                lines.push(format!("{} {} {} {}", s.ship_id, s.pos.x, s.pos.y, s.halite));
+            }
+            for (d_id, d) in dropoffs {
+                lines.push(format!("{} {} {}", d_id, d.pos.x, d.pos.y));
+            }
+        }
+        lines.push(self.changed_cells.len().to_string());
+        for p in &self.changed_cells {
+            lines.push(format!("{} {} {}", p.x, p.y, self.halite_at(*p)));
+        }
+        lines
+    }
+
+    // the Game player `pid` holds right after the pre-game handshake
+    pub fn init_game(&self, pid: usize) -> Game {
+        let mut init_lines = LineReader::new(self.init_lines(pid).into_iter());
+        let constants = ::serde_json::from_str(&init_lines.next_line("constants").unwrap()).unwrap();
+        parse_line_of_nums(&mut init_lines, "player info", 2).unwrap();
+        Game::init(&mut init_lines, constants, self.num_players, pid)
+            .expect("engine wrote an invalid pre-game")
+    }
+
+    // what player `pid` would have parsed from stdin for the upcoming turn
+    pub fn game_view(&self, pid: usize) -> Game {
+        let mut game = self.init_game(pid);
+        game.update_frame(&mut LineReader::new(self.frame_lines().into_iter()))
+            .expect("engine wrote an invalid frame");
+        game
+    }
+
+    pub fn changed_cells(&self) -> &[Point] {
+        &self.changed_cells
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn halite_at(&self, pos: Point) -> usize {
+        *self.halite_map.get(pos)
+    }
+
+    fn set_halite(&mut self, pos: Point, halite: usize) {
+        *self.halite_map.get_mut(pos) = halite;
+        if !self.changed_cells.contains(&pos) {
+            self.changed_cells.push(pos);
+        }
+    }
+
+    fn normalize(&self, pos: Point) -> Point {
+        let width = self.width as isize;
+        let height = self.height as isize;
+        Point { x: ((pos.x % width) + width) % width, y: ((pos.y % height) + height) % height }
+    }
+
+    fn dist(&self, pos1: Point, pos2: Point) -> usize {
+        let dx = (pos1.x - pos2.x).unsigned_abs();
+        let dy = (pos1.y - pos2.y).unsigned_abs();
+        min(dx, self.width - dx) + min(dy, self.height - dy)
+    }
+
+    fn structure_owner(&self, pos: Point) -> Option<usize> {
+        self.factories.iter().map(|f| (f.player, f.pos))
+            .chain(self.dropoffs.iter().map(|d| (d.player, d.pos)))
+            .find(|&(_, p)| p == pos)
+            .map(|(player, _)| player)
+    }
+
+    // advance one turn given each player's (spawn, commands), indexed by player id
+    pub fn step(&mut self, player_commands: &[TurnCommands]) {
+        assert_eq!(player_commands.len(), self.num_players);
+        self.changed_cells.clear();
+        self.events.clear();
+
+        // players are handled in a seeded random order, which decides the ids of ships spawned on the same turn
+        let mut player_order: Vec<usize> = (0..self.num_players).collect();
+        self.rng.shuffle(&mut player_order);
+
+        let mut moved: HashSet<usize> = HashSet::new();
+        let mut spawned: Vec<Ship> = Vec::new();
+        for &pid in &player_order {
+            let (spawn, ref commands) = player_commands[pid];
+            let mut ids: Vec<&usize> = commands.keys().collect();
+            ids.sort();
+            for &id in ids {
+                let owned = self.ships.get(&id).is_some_and(|s| s.player == pid);
+                if !owned {
+                    continue;
+                }
+                match commands[&id] {
+                    ShipCommand::MakeDropoff() => self.construct(id),
+                    ShipCommand::MoveShip(dir) => {
+                        if self.move_ship(id, dir) {
+                            moved.insert(id);
+                        }
+                    },
+                }
+            }
+            if spawn && self.energy[pid] >= self.constants.ship_cost {
+                self.energy[pid] -= self.constants.ship_cost;
+                self.stats[pid].ships_built += 1;
+                spawned.push(Ship {
+                    player: pid,
+                    ship_id: self.next_ship_id,
+                    pos: self.factories[pid].pos,
+                    halite: 0,
+                });
+                self.next_ship_id += 1;
+            }
+        }
+        for s in spawned {
+            self.events.push(Event::Spawn { player: s.player, ship_id: s.ship_id, pos: s.pos });
+            self.ships.insert(s.ship_id, s);
+        }
+
+        self.resolve_collisions();
+        if self.constants.capture_enabled {
+            self.capture();
+        }
+        self.deposit();
+        self.inspired = self.ships.values()
+            .filter(|s| self.is_inspired(s))
+            .map(|s| s.ship_id)
+            .collect();
+        self.mine(&moved);
+        self.turn_number += 1;
+    }
+
+    fn construct(&mut self, id: usize) {
+        let ship = self.ships[&id].clone();
+        if self.structure_owner(ship.pos).is_some() {
+            return;
+        }
+        let discount = ship.halite as usize + self.halite_at(ship.pos);
+        let cost = self.constants.dropoff_cost.saturating_sub(discount);
+        if self.energy[ship.player] >= cost {
+            self.energy[ship.player] -= cost;
+            self.stats[ship.player].dropoffs_built += 1;
+            self.events.push(Event::Construct { player: ship.player, dropoff_id: self.dropoffs.len(), pos: ship.pos });
+            self.dropoffs.push(Dropoff { player: ship.player, pos: ship.pos });
+            self.set_halite(ship.pos, 0);
+            self.ships.remove(&id);
+        }
+    }
+
+    // returns false if the ship ends up staying where it is
+    fn move_ship(&mut self, id: usize, dir: Direction) -> bool {
+        if dir == Direction::Still {
+            return false;
+        }
+        let pos = self.ships[&id].pos;
+        let cost = self.constants.move_cost(self.halite_at(pos), self.inspired.contains(&id)) as isize;
+        let (dx, dy) = match dir {
+            Direction::North => (0, -1),
+            Direction::South => (0, 1),
+            Direction::East => (1, 0),
+            Direction::West => (-1, 0),
+            Direction::Still => (0, 0),
+        };
+        let new_pos = self.normalize(Point { x: pos.x + dx, y: pos.y + dy });
+        let ship = self.ships.get_mut(&id).unwrap();
+        if ship.halite < cost {
+            return false;
+        }
+        ship.halite -= cost;
+        ship.pos = new_pos;
+        true
+    }
+
+    // every ship on a contested tile is destroyed, and the cargo is dropped on the
+    // tile (or credited to the owner if the tile holds a factory or dropoff)
+    fn resolve_collisions(&mut self) {
+        let mut by_pos: HashMap<Point, Vec<usize>> = HashMap::new();
+        for (&id, s) in &self.ships {
+            by_pos.entry(s.pos).or_default().push(id);
+        }
+        let mut crashes: Vec<(Point, Vec<usize>)> = by_pos.into_iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .collect();
+        crashes.sort_by_key(|&(p, _)| (p.y, p.x));
+
+        for (pos, ids) in crashes {
+            let mut cargo = 0;
+            for &id in &ids {
+                let s = self.ships.remove(&id).unwrap();
+                cargo += s.halite as usize;
+                self.stats[s.player].collisions += 1;
+            }
+            self.events.push(Event::Shipwreck { pos, ships: ids });
+            match self.structure_owner(pos) {
+                Some(owner) => {
+                    self.energy[owner] += cargo;
+                    self.stats[owner].halite_deposited += cargo;
+                },
+                None => {
+                    let h = self.halite_at(pos) + cargo;
+                    self.set_halite(pos, h);
+                },
+            }
+        }
+    }
+
+    fn deposit(&mut self) {
+        let structures: Vec<(usize, Point)> = self.factories.iter().map(|f| (f.player, f.pos))
+            .chain(self.dropoffs.iter().map(|d| (d.player, d.pos)))
+            .collect();
+        for s in self.ships.values_mut() {
+            if s.halite > 0 && structures.contains(&(s.player, s.pos)) {
+                self.energy[s.player] += s.halite as usize;
+                self.stats[s.player].halite_deposited += s.halite as usize;
+                s.halite = 0;
+            }
+        }
+    }
+
+    // a ship switches sides when another player has enough more ships around it than its owner
+    fn capture(&mut self) {
+        let mut captures: Vec<(usize, usize)> = Vec::new();
+        for s in self.ships.values() {
+            let mut nearby = vec![0; self.num_players];
+            for o in self.ships.values() {
+                if self.dist(o.pos, s.pos) <= self.constants.capture_radius {
+                    nearby[o.player] += 1;
+                }
+            }
+            let strongest = (0..self.num_players).max_by_key(|&p| (nearby[p], self.num_players - p)).unwrap();
+            if strongest != s.player && nearby[strongest] >= nearby[s.player] + self.constants.ships_above_for_capture {
+                captures.push((s.ship_id, strongest));
+            }
+        }
+        for (id, player) in captures {
+            self.ships.get_mut(&id).unwrap().player = player;
+        }
+    }
+
+    pub fn is_inspired(&self, ship: &Ship) -> bool {
+        self.constants.inspiration_enabled && self.ships.values()
+            .filter(|o| o.player != ship.player && self.dist(o.pos, ship.pos) <= self.constants.inspiration_radius)
+            .count() >= self.constants.inspiration_ship_count
+    }
+
+    // ships that did not move extract a share of their tile
+    fn mine(&mut self, moved: &HashSet<usize>) {
+        let miners: Vec<(usize, bool)> = self.ships.values()
+            .filter(|s| !moved.contains(&s.ship_id))
+            .map(|s| (s.ship_id, self.inspired.contains(&s.ship_id)))
+            .collect();
+        for (id, inspired) in miners {
+            let (pos, cargo) = {
+                let s = &self.ships[&id];
+                (s.pos, s.halite as usize)
+            };
+            let tile = self.halite_at(pos);
+            let space = self.constants.max_halite.saturating_sub(cargo);
+            let extracted = min(self.constants.extracted(tile, inspired), space);
+            if extracted == 0 {
+                continue;
+            }
+            let bonus = if inspired {
+                min(self.constants.inspiration_bonus(extracted), space - extracted)
+            } else { 0 };
+            self.set_halite(pos, tile - extracted);
+            self.ships.get_mut(&id).unwrap().halite += (extracted + bonus) as isize;
+        }
+    }
+}
+
+// for tests that set up a position by hand instead of playing up to it
+#[cfg(test)]
+impl Engine {
+    // factories spread along the middle row of a width x height map holding `halite` on every tile
+    pub fn flat(num_players: usize, width: usize, height: usize, halite: usize) -> Engine {
+        let factories = (0..num_players)
+            .map(|player| Factory { player, pos: Point { x: (width*(2*player + 1)/(2*num_players)) as isize, y: (height/2) as isize } })
+            .collect();
+        Engine::new(Constants::defaults(width, height), factories, GMap::new(width, height, halite), 1)
+    }
+
+    pub fn place_ship(&mut self, player: usize, x: isize, y: isize, halite: isize) -> usize {
+        let ship_id = self.next_ship_id;
+        self.next_ship_id += 1;
+        self.ships.insert(ship_id, Ship { player, ship_id, pos: Point { x, y }, halite });
+        ship_id
+    }
+
+    // reads the upcoming frame into a game that has followed the match so far
+    pub fn update_view(&self, game: &mut Game) {
+        game.update_frame(&mut LineReader::new(self.frame_lines().into_iter()))
+            .expect("engine wrote an invalid frame");
+    }
+}
@@ -0,0 +1,501 @@
+use std::collections::HashMap;
+use disjoint_set::DisjointSet;
+use game::{Frame, Game};
+use data::{Direction, Point, Ship};
+
+// Something that happened between two frames. The protocol only sends where ships are,
+// so these are reconstructed from positions, cargo and the halite map.
+#[derive(Debug, Clone)]
+pub enum FrameEvent {
+    Moved { player: usize, ship_id: usize, from: Point, dir: Direction }, // Still for ships that stayed
+    Spawned { player: usize, ship_id: usize, pos: Point },
+    Constructed { player: usize, ship_id: usize, pos: Point },
+    Deposited { player: usize, ship_id: usize, pos: Point, halite: usize },
+    // the ships as they were in the previous frame, and the cargo they had left when they
+    // hit; pos is None when nothing tells where it happened
+    Collision { pos: Option<Point>, ships: Vec<Ship>, halite_lost: usize },
+}
+
+// The frame before the current one, from the newest frame in game.history and the halite
+// map as it was before this frame's map updates.
+pub struct PrevFrame<'a> {
+    game: &'a Game,
+    pub frame: &'a Frame,
+    ship_map: HashMap<Point, usize>,
+    halite_changes: HashMap<Point, isize>,
+}
+
+impl<'a> PrevFrame<'a> {
+    // None unless the history holds the turn right before the current one
+    pub fn of(game: &'a Game) -> Option<PrevFrame<'a>> {
+        let frame = game.history.back().filter(|f| f.turn_number + 1 == game.turn_number)?;
+        Some(PrevFrame {
+            game,
+            frame,
+            ship_map: frame.ships.values().map(|s| (s.pos, s.ship_id)).collect(),
+            halite_changes: game.halite_changes.iter().cloned().collect(),
+        })
+    }
+
+    pub fn ship_at(&self, pos: Point) -> Option<&'a Ship> {
+        let frame = self.frame;
+        self.ship_map.get(&pos).map(|id| &frame.ships[id])
+    }
+
+    pub fn halite_at(&self, pos: Point) -> usize {
+        (self.game.halite_at(pos) as isize - self.halite_changes.get(&pos).cloned().unwrap_or(0)) as usize
+    }
+
+    // what a ship pays for leaving its tile, whoever it belongs to
+    pub fn move_cost(&self, ship: &Ship) -> isize {
+        let c = &self.game.constants;
+        let inspired = c.inspiration_enabled && self.frame.ships.values()
+            .filter(|o| o.player != ship.player && self.game.dist(o.pos, ship.pos) <= c.inspiration_radius)
+            .count() >= c.inspiration_ship_count;
+        c.move_cost(self.halite_at(ship.pos), inspired) as isize
+    }
+
+    // a ship that could not pay for moving off its tile had to stay
+    pub fn must_stay(&self, ship: &Ship) -> bool {
+        self.move_cost(ship) > ship.halite
+    }
+}
+
+// Every ship's move, spawn, dropoff, deposit and collision from the frame before to the
+// current one, none if game.history does not have it. Events come in ship id order,
+// collisions last.
+pub fn diff_frames(game: &Game) -> Vec<FrameEvent> {
+    let prev = match PrevFrame::of(game) {
+        Some(prev) => prev,
+        None => return Vec::new(),
+    };
+    let mut events = Vec::new();
+    let mut prev_ids: Vec<usize> = prev.frame.ships.keys().cloned().collect();
+    prev_ids.sort();
+    let mut destroyed = Vec::new();
+    for id in prev_ids {
+        let before = &prev.frame.ships[&id];
+        let (player, ship_id) = (before.player, before.ship_id);
+        match game.ships.get(&id) {
+            Some(after) => {
+                let dir = match Direction::all_directions().into_iter().find(|&d| game.step_toward(before.pos, d) == after.pos) {
+                    Some(d) => d,
+                    None => {
+                        warn!("ship {} went from ({}, {}) to ({}, {}) in one turn", id, before.pos.x, before.pos.y, after.pos.x, after.pos.y);
+                        continue;
+                    },
+                };
+                events.push(FrameEvent::Moved { player, ship_id, from: before.pos, dir });
+                let carried = before.halite - if dir == Direction::Still { 0 } else { prev.move_cost(before) };
+                if carried > 0 && after.halite == 0 && game.drop_pts_of(player).contains(&after.pos) {
+                    events.push(FrameEvent::Deposited { player, ship_id, pos: after.pos, halite: carried as usize });
+                }
+            },
+            None => {
+                let built = game.dropoffs.iter()
+                    .any(|d| d.player == player && d.pos == before.pos)
+                    && !prev.frame.dropoffs.iter().any(|d| d.pos == before.pos);
+                if built {
+                    events.push(FrameEvent::Constructed { player, ship_id, pos: before.pos });
+                } else {
+                    destroyed.push(before);
+                }
+            },
+        }
+    }
+
+    let mut new_ids: Vec<usize> = game.ships.keys().filter(|id| !prev.frame.ships.contains_key(id)).cloned().collect();
+    new_ids.sort();
+    for id in new_ids {
+        let ship = &game.ships[&id];
+        events.push(FrameEvent::Spawned { player: ship.player, ship_id: id, pos: ship.pos });
+    }
+
+    let credits = collision_credits(&prev, game, &events);
+    events.extend(collisions(&prev, game, &destroyed, &credits));
+    events
+}
+
+// What collisions on each player's drop points credited it, from how its energy changed
+// besides deposits, dropoffs and ships that showed up: one amount, or without a new ship
+// on its factory also the amount if it did spawn one that was hit right away.
+fn collision_credits(prev: &PrevFrame, game: &Game, events: &[FrameEvent]) -> Vec<Vec<isize>> {
+    let c = &game.constants;
+    (0..game.num_players)
+        .map(|player| {
+            let mut credit = game.energy[player] as isize - prev.frame.energy[player] as isize;
+            let mut spawned = false;
+            for e in events {
+                match *e {
+                    FrameEvent::Deposited { player: p, halite, .. } if p == player => credit -= halite as isize,
+                    FrameEvent::Constructed { player: p, ship_id, pos } if p == player => {
+                        let discount = prev.frame.ships[&ship_id].halite as usize + prev.halite_at(pos);
+                        credit += c.dropoff_cost.saturating_sub(discount) as isize;
+                    },
+                    FrameEvent::Spawned { player: p, .. } if p == player => {
+                        credit += c.ship_cost as isize;
+                        spawned = true;
+                    },
+                    _ => {},
+                }
+            }
+            if spawned { vec![credit] } else { vec![credit, credit + c.ship_cost as isize] }
+        })
+        .collect()
+}
+
+// Sorts the destroyed ships into collisions. All ships that end a turn on the same tile
+// are destroyed, so no ship is destroyed alone except by a ship spawned on its factory.
+// Their cargo is dropped on the tile, so the halite a tile gained is exactly what the
+// ships that hit there carried, or credited to the owner of a drop point, which credits
+// tells. collisions() searches for the assignment of ships to tiles that fits all of
+// that, separately for each set of ships that could have met or been credited to the
+// same player. Empty ships leave no trace, so where they could have hit several others
+// the first fit in CollisionSearch's order is taken. Ships that fit nowhere, or are left
+// over when a set is too large to search through, are reported without a position.
+fn collisions(prev: &PrevFrame, game: &Game, destroyed: &[&Ship], credits: &[Vec<isize>]) -> Vec<FrameEvent> {
+    let n = destroyed.len();
+    // where each ship could have been at the end of the turn; not where a ship survived
+    let reach: Vec<Vec<Point>> = destroyed.iter()
+        .map(|s| {
+            let mut tiles = vec![s.pos];
+            if !prev.must_stay(s) {
+                tiles.extend(game.neighbors(s.pos));
+            }
+            tiles.retain(|p| !game.ship_map.contains_key(p));
+            tiles
+        })
+        .collect();
+    // what ship i drops on tile p
+    let dropped = |i: usize, p: Point| {
+        let s = destroyed[i];
+        (s.halite - if p == s.pos { 0 } else { prev.move_cost(s) }).max(0) as usize
+    };
+    let mut tiles: Vec<Point> = reach.iter().flat_map(|r| r.iter().cloned()).collect();
+    tiles.sort_by_key(|p| (p.y, p.x));
+    tiles.dedup();
+    let kinds: Vec<TileKind> = tiles.iter()
+        .map(|&p| match game.factories.iter().find(|f| f.pos == p) {
+            Some(f) => TileKind::DropPoint { owner: f.player, factory: true },
+            None => match game.dropoffs.iter().find(|d| d.pos == p) {
+                Some(d) => TileKind::DropPoint { owner: d.player, factory: false },
+                None => TileKind::Gained((game.halite_at(p) as isize - prev.halite_at(p) as isize).max(0) as usize),
+            },
+        })
+        .collect();
+
+    let tile_index = |p: &Point| tiles.binary_search_by_key(&(p.y, p.x), |t| (t.y, t.x)).unwrap();
+    let mut ds = DisjointSet::make_singletons(n + tiles.len());
+    for (i, tiles_reached) in reach.iter().enumerate() {
+        for p in tiles_reached {
+            ds.unite(i, n + tile_index(p));
+        }
+    }
+    for t in 0..tiles.len() {
+        for u in 0..t {
+            if let (TileKind::DropPoint { owner, .. }, TileKind::DropPoint { owner: other, .. }) = (kinds[t], kinds[u]) {
+                if owner == other {
+                    ds.unite(n + t, n + u);
+                }
+            }
+        }
+    }
+    let mut ending: Vec<Option<usize>> = vec![None; n];
+    for root in 0..n {
+        let ships: Vec<usize> = (0..n).filter(|&i| ds.find(i) == ds.find(root)).collect();
+        if ships[0] != root {
+            continue;
+        }
+        let mut search = CollisionSearch {
+            options: ships.iter()
+                .map(|&i| {
+                    let mut options: Vec<(usize, usize)> = reach[i].iter().map(|p| (tile_index(p), dropped(i, *p))).collect();
+                    // drop points first, then tiles where halite appeared, then staying put
+                    options.sort_by_key(|&(t, _)| match kinds[t] {
+                        TileKind::DropPoint { .. } => 0,
+                        TileKind::Gained(g) if g > 0 => 1,
+                        _ => if tiles[t] == destroyed[i].pos { 2 } else { 3 },
+                    });
+                    options
+                })
+                .collect(),
+            kinds: &kinds,
+            credits,
+            owners: Vec::new(),
+            last_to_reach: HashMap::new(),
+            count: vec![0; tiles.len()],
+            sum: vec![0; tiles.len()],
+            chosen: vec![None; ships.len()],
+            best: vec![None; ships.len()],
+            best_penalty: usize::MAX,
+            penalty: 0,
+            nodes: 0,
+        };
+        for (k, options) in search.options.iter().enumerate() {
+            for &(t, _) in options {
+                search.last_to_reach.insert(t, k);
+                if let TileKind::DropPoint { owner, .. } = kinds[t] {
+                    search.owners.push(owner);
+                }
+            }
+        }
+        search.owners.sort();
+        search.owners.dedup();
+        search.run(0);
+        for (k, &i) in ships.iter().enumerate() {
+            ending[i] = search.best[k];
+        }
+    }
+
+    let mut events = Vec::new();
+    for (t, &p) in tiles.iter().enumerate() {
+        let members: Vec<usize> = (0..n).filter(|&i| ending[i] == Some(t)).collect();
+        if !members.is_empty() {
+            events.push(FrameEvent::Collision {
+                pos: Some(p),
+                halite_lost: members.iter().map(|&i| dropped(i, p)).sum(),
+                ships: members.iter().map(|&i| destroyed[i].clone()).collect(),
+            });
+        }
+    }
+    events.extend((0..n).filter(|&i| ending[i].is_none()).map(|i| FrameEvent::Collision {
+        pos: None,
+        halite_lost: destroyed[i].halite.max(0) as usize,
+        ships: vec![destroyed[i].clone()],
+    }));
+    events
+}
+
+#[derive(Clone, Copy)]
+enum TileKind {
+    DropPoint { owner: usize, factory: bool },
+    Gained(usize), // halite that appeared on the tile
+}
+
+// more than enough for the sets of ships a turn of a real game destroys together
+const MAX_SEARCH_NODES: usize = 100_000;
+// what an assignment pays for each ship it cannot place, and for each player whose drop
+// points it credits other than the player's energy tells
+const UNPLACED_PENALTY: usize = 1000;
+const CREDIT_PENALTY: usize = 100;
+
+// Depth first search over where each of a set of destroyed ships ended the turn, for the
+// assignment that places the most ships on tiles that fit what happened there.
+struct CollisionSearch<'a> {
+    options: Vec<Vec<(usize, usize)>>, // per ship, the tiles it could have reached and what it drops there
+    kinds: &'a [TileKind],
+    credits: &'a [Vec<isize>],
+    owners: Vec<usize>, // the players whose drop points the ships can reach
+    last_to_reach: HashMap<usize, usize>, // the last ship in the search that can end on a tile
+    count: Vec<usize>, // ships on each tile so far
+    sum: Vec<usize>, // and their cargo
+    chosen: Vec<Option<usize>>,
+    best: Vec<Option<usize>>,
+    best_penalty: usize,
+    penalty: usize,
+    nodes: usize,
+}
+
+impl<'a> CollisionSearch<'a> {
+    // whether the ships on t, if no more can join them, could all have been destroyed there
+    fn fits(&self, t: usize) -> bool {
+        match (self.kinds[t], self.count[t]) {
+            (TileKind::Gained(g), 0) => g == 0,
+            (_, 0) => true,
+            (TileKind::DropPoint { factory, .. }, count) => count >= 2 || factory,
+            (TileKind::Gained(g), count) => count >= 2 && self.sum[t] == g,
+        }
+    }
+
+    // how many players the collisions chosen credit other than credits says
+    fn credit_misfits(&self) -> usize {
+        let mut credited = vec![0; self.credits.len()];
+        let mut spawn_hit = vec![false; self.credits.len()];
+        for (t, &kind) in self.kinds.iter().enumerate() {
+            if let TileKind::DropPoint { owner, factory } = kind {
+                credited[owner] += self.sum[t] as isize;
+                spawn_hit[owner] |= factory && self.count[t] == 1;
+            }
+        }
+        self.owners.iter()
+            .filter(|&&p| self.credits[p].get(spawn_hit[p] as usize) != Some(&credited[p]))
+            .count()
+    }
+
+    fn run(&mut self, k: usize) {
+        self.nodes += 1;
+        if self.nodes > MAX_SEARCH_NODES || self.penalty >= self.best_penalty {
+            return;
+        }
+        if k == self.options.len() {
+            let penalty = self.penalty + CREDIT_PENALTY*self.credit_misfits();
+            if penalty < self.best_penalty {
+                self.best = self.chosen.clone();
+                self.best_penalty = penalty;
+            }
+            return;
+        }
+        for o in 0..self.options[k].len() {
+            let (t, drop) = self.options[k][o];
+            if let TileKind::Gained(g) = self.kinds[t] {
+                if self.sum[t] + drop > g {
+                    continue;
+                }
+            }
+            self.count[t] += 1;
+            self.sum[t] += drop;
+            self.chosen[k] = Some(t);
+            self.run_if_settled(k);
+            self.count[t] -= 1;
+            self.sum[t] -= drop;
+            if self.best_penalty == 0 {
+                return;
+            }
+        }
+        self.chosen[k] = None;
+        self.penalty += UNPLACED_PENALTY;
+        self.run_if_settled(k);
+        self.penalty -= UNPLACED_PENALTY;
+    }
+
+    // goes on to the next ship unless a tile no later ship can reach is left not fitting
+    fn run_if_settled(&mut self, k: usize) {
+        let settled = self.options[k].iter()
+            .all(|&(t, _)| self.last_to_reach[&t] != k || self.fits(t));
+        if settled {
+            self.run(k + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use rand::{Rng, SeedableRng};
+    use rand::prng::XorShiftRng;
+    use data::ShipCommand;
+    use engine::{Engine, Event};
+    use game::Constants;
+    use mapgen;
+
+    // Steps the engine and checks that diff_frames, from what the protocol shows player 0,
+    // finds every move, spawn, dropoff and collision the engine made. Returns the tiles of
+    // the collisions.
+    fn step_and_check(engine: &mut Engine, game: &mut Game, commands: &[(bool, HashMap<usize, ShipCommand>)]) -> Vec<Point> {
+        let before = engine.ships.clone();
+        engine.step(commands);
+        engine.update_view(game);
+        let events = diff_frames(game);
+
+        let mut moved = HashMap::new();
+        let (mut spawns, mut constructs, mut collisions) = (HashSet::new(), HashSet::new(), HashSet::new());
+        for e in &events {
+            match *e {
+                FrameEvent::Moved { player, ship_id, from, dir } => {
+                    assert_eq!((player, from), (before[&ship_id].player, before[&ship_id].pos));
+                    moved.insert(ship_id, game.step_toward(from, dir));
+                },
+                FrameEvent::Spawned { player, ship_id, pos } => { spawns.insert((player, ship_id, pos)); },
+                FrameEvent::Constructed { player, pos, .. } => { constructs.insert((player, pos)); },
+                FrameEvent::Collision { pos, ref ships, .. } => {
+                    let mut ids: Vec<usize> = ships.iter().map(|s| s.ship_id).collect();
+                    ids.sort();
+                    collisions.insert((pos, ids));
+                },
+                FrameEvent::Deposited { .. } => {},
+            }
+        }
+        let survived: HashMap<usize, Point> = engine.ships.iter()
+            .filter(|(id, _)| before.contains_key(id))
+            .map(|(&id, s)| (id, s.pos))
+            .collect();
+        assert_eq!(moved, survived, "turn {}: moves", game.turn_number);
+
+        let (mut true_spawns, mut true_constructs, mut true_collisions) = (HashSet::new(), HashSet::new(), HashSet::new());
+        for e in engine.events() {
+            match *e {
+                // unless one is hit on its factory right away
+                Event::Spawn { player, ship_id, pos } if engine.ships.contains_key(&ship_id) => {
+                    true_spawns.insert((player, ship_id, pos));
+                },
+                Event::Spawn { .. } => {},
+                Event::Construct { player, pos, .. } => { true_constructs.insert((player, pos)); },
+                Event::Shipwreck { pos, ref ships } => {
+                    // ships spawned this turn never show up in a frame
+                    let mut ids: Vec<usize> = ships.iter().cloned().filter(|id| before.contains_key(id)).collect();
+                    ids.sort();
+                    true_collisions.insert((Some(pos), ids));
+                },
+            }
+        }
+        assert_eq!(spawns, true_spawns, "turn {}: spawns", game.turn_number);
+        assert_eq!(constructs, true_constructs, "turn {}: dropoffs", game.turn_number);
+        assert_eq!(collisions, true_collisions, "turn {}: collisions", game.turn_number);
+        collisions.into_iter().filter_map(|(pos, _)| pos).collect()
+    }
+
+    fn no_commands(engine: &Engine) -> Vec<(bool, HashMap<usize, ShipCommand>)> {
+        vec![(false, HashMap::new()); engine.num_players]
+    }
+
+    #[test]
+    fn diff_frames_tells_apart_adjacent_collisions() {
+        // a and b hit on (5, 5) while c, which could have stayed there, moves east into d
+        let mut engine = Engine::flat(2, 16, 16, 0);
+        let a = engine.place_ship(0, 5, 4, 300);
+        let b = engine.place_ship(1, 4, 5, 200);
+        let c = engine.place_ship(0, 5, 5, 0);
+        let d = engine.place_ship(1, 7, 5, 100);
+        let mut game = engine.game_view(0);
+        let mut commands = no_commands(&engine);
+        commands[0].1.insert(a, ShipCommand::MoveShip(Direction::South));
+        commands[1].1.insert(b, ShipCommand::MoveShip(Direction::East));
+        commands[0].1.insert(c, ShipCommand::MoveShip(Direction::East));
+        commands[1].1.insert(d, ShipCommand::MoveShip(Direction::West));
+        let tiles = step_and_check(&mut engine, &mut game, &commands);
+        assert_eq!(tiles.len(), 2);
+    }
+
+    #[test]
+    fn diff_frames_matches_the_engine_on_random_play() {
+        let mut adjacent_turns = 0;
+        for seed in 0..8 {
+            let num_players = if seed % 2 == 0 { 2 } else { 4 };
+            let (halite_map, factories) = mapgen::generate(num_players, 16, 16, seed);
+            let mut engine = Engine::new(Constants::defaults(16, 16), factories, halite_map, seed);
+            let mut game = engine.game_view(0);
+            let mut rng = XorShiftRng::seed_from_u64(seed);
+            for turn in 0..100 {
+                // kept crowded, so that ships often meet several at a time
+                if turn % 5 == 0 {
+                    while engine.ships.len() < 100 {
+                        let (x, y) = (rng.gen_range(0, 16), rng.gen_range(0, 16));
+                        if !engine.ships.values().any(|s| s.pos == Point { x, y }) {
+                            let halite = rng.gen_range(0, 1000);
+                            engine.place_ship(rng.gen_range(0, num_players), x, y, halite);
+                        }
+                    }
+                    engine.update_view(&mut game);
+                }
+                let mut commands = no_commands(&engine);
+                for ship in engine.ships.values() {
+                    let command = if rng.gen_range(0, 50) == 0 {
+                        ShipCommand::MakeDropoff()
+                    } else {
+                        ShipCommand::MoveShip(Direction::all_directions()[rng.gen_range(0, 5)])
+                    };
+                    commands[ship.player].1.insert(ship.ship_id, command);
+                }
+                for c in commands.iter_mut() {
+                    c.0 = rng.gen_range(0, 3) == 0;
+                }
+                let tiles = step_and_check(&mut engine, &mut game, &commands);
+                if tiles.iter().any(|&p| tiles.iter().any(|&q| game.dist(p, q) == 1)) {
+                    adjacent_turns += 1;
+                }
+            }
+        }
+        assert!(adjacent_turns >= 20, "only {} turns with collisions on adjacent tiles", adjacent_turns);
+    }
+}
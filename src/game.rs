@@ -1,14 +1,26 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::collections::BinaryHeap;
-use std::iter::FromIterator;
+use std::collections::VecDeque;
 use std::isize;
 use data::*;
+use distance::{DistanceField, HomeField, unit_cost};
+use halite_sums::HaliteSums;
 use serde_json::Value;
 use std::cmp::min;
 use std::cmp::max;
 use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::ops::{Index, IndexMut};
 
+// how many past frames Game::history keeps
+pub const HISTORY_LEN: usize = 50;
+
+// halite a turn of mining should extract for a tile to be worth stopping on
+const MIN_MINING_YIELD: usize = 25;
+
+#[derive(Clone)]
 pub struct Game {
 	pub turn_number: usize,
     pub max_turns: usize,
@@ -19,6 +31,7 @@ pub struct Game {
     pub width: usize,
     pub height: usize,
     pub halite_map: GMap<usize>,
+    pub halite_sums: HaliteSums, // window sums of halite_map
 
     pub ships: HashMap<usize, Ship>,
     pub ship_id_by_player: Vec<Vec<usize>>,
@@ -26,36 +39,308 @@ pub struct Game {
     pub dropoffs: Vec<Dropoff>,
     pub energy: Vec<usize>,
     pub my_drop_pts: Vec<Point>,
-    pub nearest_drop_pt_idx: GMap<usize>,
-    pub nearest_drop_pt_dist: GMap<usize>,
+    pub my_drop_field: DistanceField, // moves to the nearest of my_drop_pts
+    pub home: HomeField, // what going home costs from each tile this turn
     pub enemy_drop_pts: HashMap<Point, usize>,
+    pub inspired: GMap<bool>, // tiles where a ship of ours would be inspired, given the enemy ships this turn
+    pub halite_changes: Vec<(Point, isize)>, // the map updates that came with this frame
+    pub history: VecDeque<Frame>, // the frames before this one, oldest first
+}
+
+// What update_frame had read for an earlier turn.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub turn_number: usize,
+    pub ships: HashMap<usize, Ship>,
+    pub dropoffs: Vec<Dropoff>,
+    pub energy: Vec<usize>,
+    pub halite_changes: Vec<(Point, isize)>,
+}
+
+impl Frame {
+    fn to_json(&self) -> Value {
+        let mut ships: Vec<&Ship> = self.ships.values().collect();
+        ships.sort_by_key(|s| s.ship_id);
+        json!({
+            "turn_number": self.turn_number,
+            "ships": ships.iter().map(|s| json!([s.ship_id, s.player, s.pos.x, s.pos.y, s.halite])).collect::<Vec<Value>>(),
+            "dropoffs": self.dropoffs.iter().map(|d| json!([d.player, d.pos.x, d.pos.y])).collect::<Vec<Value>>(),
+            "energy": self.energy,
+            "halite_changes": changes_to_json(&self.halite_changes),
+        })
+    }
+
+    fn from_json(v: &Value) -> Result<Frame, String> {
+        fn num(v: &Value) -> Result<isize, String> {
+            v.as_i64().map(|n| n as isize).ok_or_else(|| format!("expected a number, got {}", v))
+        }
+        fn list(v: &Value) -> Result<&Vec<Value>, String> {
+            v.as_array().ok_or_else(|| format!("expected a list, got {}", v))
+        }
+
+        let mut ships = HashMap::new();
+        for s in list(&v["ships"])? {
+            let ship = Ship {
+                ship_id: num(&s[0])? as usize,
+                player: num(&s[1])? as usize,
+                pos: Point { x: num(&s[2])?, y: num(&s[3])? },
+                halite: num(&s[4])?,
+            };
+            ships.insert(ship.ship_id, ship);
+        }
+        let dropoffs = list(&v["dropoffs"])?.iter()
+            .map(|d| Ok(Dropoff { player: num(&d[0])? as usize, pos: Point { x: num(&d[1])?, y: num(&d[2])? } }))
+            .collect::<Result<Vec<Dropoff>, String>>()?;
+        let energy = list(&v["energy"])?.iter()
+            .map(|e| num(e).map(|e| e as usize))
+            .collect::<Result<Vec<usize>, String>>()?;
+        Ok(Frame {
+            turn_number: num(&v["turn_number"])? as usize,
+            ships,
+            dropoffs,
+            energy,
+            halite_changes: changes_from_json(&v["halite_changes"])?,
+        })
+    }
+}
+
+// A frame as read by Game::read_frame, before any of it is applied.
+struct FrameInput {
+    turn: usize, // as the engine counts, from 1
+    energy: Vec<usize>,
+    ships: Vec<Ship>, // player by player, in the order they were listed
+    dropoffs: Vec<Dropoff>,
+    map_updates: Vec<(Point, usize)>,
+}
+
+fn changes_to_json(changes: &[(Point, isize)]) -> Value {
+    json!(changes.iter().map(|(p, d)| json!([p.x, p.y, d])).collect::<Vec<Value>>())
 }
 
+fn changes_from_json(v: &Value) -> Result<Vec<(Point, isize)>, String> {
+    v.as_array().ok_or_else(|| format!("expected a list, got {}", v))?
+        .iter()
+        .map(|c| match (c[0].as_i64(), c[1].as_i64(), c[2].as_i64()) {
+            (Some(x), Some(y), Some(d)) => Ok((Point { x: x as isize, y: y as isize }, d as isize)),
+            _ => Err(format!("expected [x, y, change], got {}", c)),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
 pub struct Constants {
     pub max_turns: usize,
     pub ship_cost: usize,
     pub dropoff_cost: usize,
     pub max_halite: usize,
+    pub initial_energy: usize,
+    pub move_cost_ratio: usize,
+    pub extract_ratio: usize,
+    pub inspiration_enabled: bool,
+    pub inspiration_radius: usize,
+    pub inspiration_ship_count: usize,
+    pub inspired_extract_ratio: usize,
+    pub inspired_bonus_multiplier: f64,
+    pub inspired_move_cost_ratio: usize,
+    pub capture_enabled: bool,
+    pub capture_radius: usize,
+    pub ships_above_for_capture: usize,
+}
+
+impl Constants {
+    // The constants are always the first line the engine sends. The four
+    // original fields are required, anything else falls back to the default.
+    pub fn from_json(constant_json: &Value) -> Result<Constants, ParseError> {
+        let defaults = Constants::defaults(0, 0);
+        let invalid = |key: &str| ParseError {
+            line: 1,
+            expected: format!("constant {}", key),
+            raw: Some(constant_json.to_string()),
+        };
+        let field = |key: &str| constant_json[key].as_u64().map(|n| n as usize)
+            .ok_or_else(|| invalid(key));
+        let num_or = |key: &str, default: usize| match constant_json.get(key) {
+            Some(v) => v.as_u64().map(|n| n as usize).ok_or_else(|| invalid(key)),
+            None => Ok(default),
+        };
+        let float_or = |key: &str, default: f64| match constant_json.get(key) {
+            Some(v) => v.as_f64().ok_or_else(|| invalid(key)),
+            None => Ok(default),
+        };
+        let bool_or = |key: &str, default: bool| match constant_json.get(key) {
+            Some(v) => v.as_bool().ok_or_else(|| invalid(key)),
+            None => Ok(default),
+        };
+
+        Ok(Constants {
+            max_turns: field("MAX_TURNS")?,
+            ship_cost: field("NEW_ENTITY_ENERGY_COST")?,
+            dropoff_cost: field("DROPOFF_COST")?,
+            max_halite: field("MAX_ENERGY")?,
+            initial_energy: num_or("INITIAL_ENERGY", defaults.initial_energy)?,
+            move_cost_ratio: num_or("MOVE_COST_RATIO", defaults.move_cost_ratio)?,
+            extract_ratio: num_or("EXTRACT_RATIO", defaults.extract_ratio)?,
+            inspiration_enabled: bool_or("INSPIRATION_ENABLED", defaults.inspiration_enabled)?,
+            inspiration_radius: num_or("INSPIRATION_RADIUS", defaults.inspiration_radius)?,
+            inspiration_ship_count: num_or("INSPIRATION_SHIP_COUNT", defaults.inspiration_ship_count)?,
+            inspired_extract_ratio: num_or("INSPIRED_EXTRACT_RATIO", defaults.inspired_extract_ratio)?,
+            inspired_bonus_multiplier: float_or("INSPIRED_BONUS_MULTIPLIER", defaults.inspired_bonus_multiplier)?,
+            inspired_move_cost_ratio: num_or("INSPIRED_MOVE_COST_RATIO", defaults.inspired_move_cost_ratio)?,
+            capture_enabled: bool_or("CAPTURE_ENABLED", defaults.capture_enabled)?,
+            capture_radius: num_or("CAPTURE_RADIUS", defaults.capture_radius)?,
+            ships_above_for_capture: num_or("SHIPS_ABOVE_FOR_CAPTURE", defaults.ships_above_for_capture)?,
+        })
+    }
+
+    // official engine defaults, MAX_TURNS scales from 400 (32x32) to 500 (64x64)
+    pub fn defaults(width: usize, height: usize) -> Constants {
+        let size = max(width, height);
+        Constants {
+            max_turns: 300 + 25*size/8,
+            ship_cost: 1000,
+            dropoff_cost: 4000,
+            max_halite: 1000,
+            initial_energy: 5000,
+            move_cost_ratio: 10,
+            extract_ratio: 4,
+            inspiration_enabled: true,
+            inspiration_radius: 4,
+            inspiration_ship_count: 2,
+            inspired_extract_ratio: 4,
+            inspired_bonus_multiplier: 2.0,
+            inspired_move_cost_ratio: 10,
+            capture_enabled: false,
+            capture_radius: 3,
+            ships_above_for_capture: 3,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "MAX_TURNS": self.max_turns,
+            "NEW_ENTITY_ENERGY_COST": self.ship_cost,
+            "DROPOFF_COST": self.dropoff_cost,
+            "MAX_ENERGY": self.max_halite,
+            "INITIAL_ENERGY": self.initial_energy,
+            "MOVE_COST_RATIO": self.move_cost_ratio,
+            "EXTRACT_RATIO": self.extract_ratio,
+            "INSPIRATION_ENABLED": self.inspiration_enabled,
+            "INSPIRATION_RADIUS": self.inspiration_radius,
+            "INSPIRATION_SHIP_COUNT": self.inspiration_ship_count,
+            "INSPIRED_EXTRACT_RATIO": self.inspired_extract_ratio,
+            "INSPIRED_BONUS_MULTIPLIER": self.inspired_bonus_multiplier,
+            "INSPIRED_MOVE_COST_RATIO": self.inspired_move_cost_ratio,
+            "CAPTURE_ENABLED": self.capture_enabled,
+            "CAPTURE_RADIUS": self.capture_radius,
+            "SHIPS_ABOVE_FOR_CAPTURE": self.ships_above_for_capture,
+        })
+    }
+
+    // halite burned leaving a tile, rounded down
+    pub fn move_cost(&self, tile_halite: usize, inspired: bool) -> usize {
+        tile_halite / if inspired { self.inspired_move_cost_ratio } else { self.move_cost_ratio }
+    }
+
+    // cargo at which a ship stops mining and heads home, 950 of the default 1000
+    pub fn return_cargo(&self) -> usize {
+        self.max_halite - self.max_halite/20
+    }
+
+    // tile halite a ship stops to mine, 100 with the default extract ratio
+    pub fn min_mining_halite(&self) -> usize {
+        MIN_MINING_YIELD*self.extract_ratio
+    }
+
+    // halite taken off a tile by one turn of mining, rounded up
+    pub fn extracted(&self, tile_halite: usize, inspired: bool) -> usize {
+        tile_halite.div_ceil(if inspired { self.inspired_extract_ratio } else { self.extract_ratio })
+    }
+
+    // extra halite an inspired ship receives on top of what it extracted
+    pub fn inspiration_bonus(&self, extracted: usize) -> usize {
+        (extracted as f64*self.inspired_bonus_multiplier) as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize, // 1-based, counted from the constants line
+    pub expected: String,
+    pub raw: Option<String>, // None if the input ended
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.raw {
+            Some(ref raw) => write!(f, "line {}: expected {}, got {:?}", self.line, self.expected, raw),
+            None => write!(f, "line {}: expected {}, but the input ended", self.line, self.expected),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+// the engine's lines, numbered so that parse errors can point at the bad one
+pub struct LineReader<I> {
+    lines: I,
+    line_number: usize,
+    pending: VecDeque<String>, // lines peek has read ahead, not yet counted
+}
+
+impl<I: Iterator<Item = String>> LineReader<I> {
+    pub fn new(lines: I) -> LineReader<I> {
+        LineReader { lines, line_number: 0, pending: VecDeque::new() }
+    }
+
+    pub fn next_line(&mut self, expected: &str) -> Result<String, ParseError> {
+        self.line_number += 1;
+        self.pending.pop_front().or_else(|| self.lines.next()).ok_or_else(|| ParseError {
+            line: self.line_number,
+            expected: expected.to_owned(),
+            raw: None,
+        })
+    }
+
+    // the line `ahead` lines after the next one, without consuming anything; None past the end
+    // of the input. Blocks like next_line until that line has been sent.
+    pub fn peek(&mut self, ahead: usize) -> Option<&str> {
+        while self.pending.len() <= ahead {
+            let line = self.lines.next()?;
+            self.pending.push_back(line);
+        }
+        Some(&self.pending[ahead])
+    }
+
+    // an error about the line that was read last
+    pub fn error(&self, expected: &str, raw: &str) -> ParseError {
+        ParseError { line: self.line_number, expected: expected.to_owned(), raw: Some(raw.to_owned()) }
+    }
 }
 
-pub fn parse_line_of_nums<I: Iterator<Item = String>>(lines_iter: &mut I) -> Vec<usize> {
-    lines_iter.next().unwrap()
-        .trim()
+// reads a line of exactly `count` whitespace-separated numbers
+pub fn parse_line_of_nums<I: Iterator<Item = String>>(lines: &mut LineReader<I>,
+    expected: &str, count: usize) -> Result<Vec<usize>, ParseError> {
+    let line = lines.next_line(expected)?;
+    let nums: Vec<usize> = line
         .split_whitespace()
         .map(str::parse::<usize>)
-        .map(Result::unwrap)
-        .collect()
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| lines.error(expected, &line))?;
+    if nums.len() != count {
+        return Err(lines.error(expected, &line));
+    }
+    Ok(nums)
 }
 
 impl Game {
-	pub fn init<I: Iterator<Item = String>>(lines_iter: &mut I,
-        constant_json: Value, num_players: usize, my_pid: usize) -> Game { // pre-parse
+	pub fn init<I: Iterator<Item = String>>(lines_iter: &mut LineReader<I>,
+        constant_json: Value, num_players: usize, my_pid: usize) -> Result<Game, ParseError> { // pre-parse
 
         let mut factories: Vec<Factory> = Vec::new();
         let mut my_drop_pts: Vec<Point> = Vec::new();
         let mut enemy_drop_pts: HashMap<Point, usize> = HashMap::new();
         for _ in 0..num_players {
-            let this_player = parse_line_of_nums(lines_iter);
+            let this_player = parse_line_of_nums(lines_iter, "factory \"player x y\"", 3)?;
             let f_x = this_player[1] as isize;
             let f_y = this_player[2] as isize;
             factories.push(Factory {
@@ -72,13 +357,13 @@ impl Game {
             }
         }
 
-        let dims = parse_line_of_nums(lines_iter);
+        let dims = parse_line_of_nums(lines_iter, "map size \"width height\"", 2)?;
         let width = dims[0];
         let height = dims[1];
 
         let mut h_map: Vec<Vec<usize>> = Vec::new();
         for _y in 0..height {
-            h_map.push(parse_line_of_nums(lines_iter));
+            h_map.push(parse_line_of_nums(lines_iter, "row of halite", width)?);
         }
         info!("num_players: {}, my player id: {}\n factories: {:?}",
             num_players, my_pid, factories);
@@ -87,111 +372,375 @@ impl Game {
         //     info!("{:?}", map[y]);
         // }
 
-        let max_turns = constant_json["MAX_TURNS"].as_u64().unwrap() as usize;
+        let constants = Constants::from_json(&constant_json)?;
+        let max_turns = constants.max_turns;
         info!("MAX_TURNS: {}", max_turns);
+        let halite_map = GMap::from_rows(h_map);
 
-        Game {
+        let mut game = Game {
 			turn_number: 0,
             max_turns,
-            constants: Constants {
-                max_turns,
-                ship_cost: constant_json["NEW_ENTITY_ENERGY_COST"].as_u64().unwrap() as usize,
-                dropoff_cost: constant_json["DROPOFF_COST"].as_u64().unwrap() as usize,
-                max_halite: constant_json["MAX_ENERGY"].as_u64().unwrap() as usize,
-            },
+            constants,
             num_players,
             my_pid,
             factories,
             width, height,
-            halite_map: GMap { gmap: h_map },
+            halite_sums: HaliteSums::new(&halite_map),
+            halite_map,
             ships: HashMap::new(),
             dropoffs: Vec::new(),
             ship_id_by_player: Vec::new(),
             ship_map: HashMap::new(),
             energy: vec![0; num_players],
             my_drop_pts,
-            nearest_drop_pt_idx: GMap{ gmap: vec![vec![0; width]; height] },
-            nearest_drop_pt_dist: GMap{ gmap: vec![vec![width+height+1; width]; height] },
+            my_drop_field: DistanceField::new(width, height, &[], &unit_cost),
+            home: HomeField::empty(width, height),
             enemy_drop_pts,
-		}
+            inspired: GMap::new(width, height, false),
+            halite_changes: Vec::new(),
+            history: VecDeque::new(),
+		};
+        game.update_dropoff_maps();
+        game.home = HomeField::new(&game);
+        Ok(game)
 	}
 
+    // everything update_frame has accumulated, for turn snapshots
+    pub fn to_json(&self) -> Value {
+        let ships: Vec<Value> = self.ship_id_by_player.iter().flat_map(|ids| ids.iter())
+            .map(|id| {
+                let s = &self.ships[id];
+                json!([s.ship_id, s.player, s.pos.x, s.pos.y, s.halite])
+            })
+            .collect();
+        let ship_map: Vec<Value> = self.ship_map.iter()
+            .map(|(p, id)| json!([p.x, p.y, id]))
+            .collect();
+        let dropoffs: Vec<Value> = self.dropoffs.iter()
+            .map(|d| json!([d.player, d.pos.x, d.pos.y]))
+            .collect();
+        let factories: Vec<Value> = self.factories.iter()
+            .map(|f| json!([f.player, f.pos.x, f.pos.y]))
+            .collect();
+        let enemy_drop_pts: Vec<Value> = self.enemy_drop_pts.iter()
+            .map(|(p, pid)| json!([p.x, p.y, pid]))
+            .collect();
+        json!({
+            "turn_number": self.turn_number,
+            "constants": self.constants.to_json(),
+            "num_players": self.num_players,
+            "my_pid": self.my_pid,
+            "factories": factories,
+            "width": self.width,
+            "height": self.height,
+            "halite_map": self.halite_map.to_rows(),
+            "ships": ships,
+            "ship_map": ship_map,
+            "dropoffs": dropoffs,
+            "energy": self.energy,
+            "my_drop_pts": self.my_drop_pts.iter().map(Point::to_json).collect::<Vec<Value>>(),
+            "enemy_drop_pts": enemy_drop_pts,
+            "halite_changes": changes_to_json(&self.halite_changes),
+            "history": self.history.iter().map(Frame::to_json).collect::<Vec<Value>>(),
+        })
+    }
+
+    // Snapshots are one line of JSON, so every error is on line 1. Anything that would
+    // later index out of range is checked here.
+    pub fn from_json(v: &Value) -> Result<Game, ParseError> {
+        fn invalid(expected: &str, raw: String) -> ParseError {
+            ParseError { line: 1, expected: expected.to_owned(), raw: Some(raw) }
+        }
+        fn num(v: &Value) -> Result<usize, ParseError> {
+            v.as_u64().map(|n| n as usize).ok_or_else(|| invalid("a number", v.to_string()))
+        }
+        fn nums(v: &Value) -> Result<Vec<usize>, ParseError> {
+            v.as_array().ok_or_else(|| invalid("a list", v.to_string()))?
+                .iter().map(num).collect()
+        }
+        // a list of lists that are all `len` long
+        fn rows(v: &Value, len: usize, expected: &str) -> Result<Vec<Vec<usize>>, ParseError> {
+            let rows = v.as_array().ok_or_else(|| invalid(expected, v.to_string()))?
+                .iter().map(nums).collect::<Result<Vec<Vec<usize>>, ParseError>>()?;
+            match rows.iter().position(|row| row.len() != len) {
+                Some(i) => Err(invalid(expected, v[i].to_string())),
+                None => Ok(rows),
+            }
+        }
+
+        let num_players = num(&v["num_players"])?;
+        let width = num(&v["width"])?;
+        let height = num(&v["height"])?;
+        let my_pid = num(&v["my_pid"])?;
+        if my_pid >= num_players {
+            return Err(invalid(&format!("my_pid below {}", num_players), my_pid.to_string()));
+        }
+        // the rows, once column `col` of each is known to hold a player id
+        let with_players = |rows: Vec<Vec<usize>>, col: usize| match rows.iter().find(|r| r[col] >= num_players) {
+            Some(r) => Err(invalid(&format!("a player id below {}", num_players), format!("{:?}", r))),
+            None => Ok(rows),
+        };
+        let constants = Constants::from_json(&v["constants"])?;
+        let factories: Vec<Factory> = with_players(rows(&v["factories"], 3, "factories as [player, x, y]")?, 0)?.iter()
+            .map(|f| Factory { player: f[0], pos: Point { x: f[1] as isize, y: f[2] as isize } })
+            .collect();
+        if factories.len() != num_players {
+            return Err(invalid(&format!("{} factories", num_players), v["factories"].to_string()));
+        }
+
+        let mut ships = HashMap::new();
+        let mut ship_id_by_player = vec![Vec::new(); num_players];
+        for s in v["ships"].as_array().ok_or_else(|| invalid("a list of ships", v["ships"].to_string()))? {
+            let ship = Ship {
+                ship_id: num(&s[0])?,
+                player: num(&s[1])?,
+                pos: Point { x: num(&s[2])? as isize, y: num(&s[3])? as isize },
+                halite: s[4].as_i64().ok_or_else(|| invalid("ship halite", s.to_string()))? as isize,
+            };
+            if ship.player >= num_players {
+                return Err(invalid(&format!("a player id below {}", num_players), s.to_string()));
+            }
+            ship_id_by_player[ship.player].push(ship.ship_id);
+            ships.insert(ship.ship_id, ship);
+        }
+        let ship_map = rows(&v["ship_map"], 3, "ship_map as [x, y, ship id]")?.iter()
+            .map(|e| if ships.contains_key(&e[2]) { Ok((Point { x: e[0] as isize, y: e[1] as isize }, e[2])) } else {
+                Err(invalid("a ship id from ships", format!("{:?}", e)))
+            })
+            .collect::<Result<HashMap<Point, usize>, ParseError>>()?;
+        let dropoffs = with_players(rows(&v["dropoffs"], 3, "dropoffs as [player, x, y]")?, 0)?.iter()
+            .map(|d| Dropoff { player: d[0], pos: Point { x: d[1] as isize, y: d[2] as isize } })
+            .collect();
+        let my_drop_pts = v["my_drop_pts"].as_array().ok_or_else(|| invalid("a list of my_drop_pts", v["my_drop_pts"].to_string()))?
+            .iter().map(|p| Point::from_json(p).map_err(|e| invalid("my_drop_pts as [x, y]", e)))
+            .collect::<Result<Vec<Point>, ParseError>>()?;
+        let enemy_drop_pts = with_players(rows(&v["enemy_drop_pts"], 3, "enemy_drop_pts as [x, y, player]")?, 2)?.iter()
+            .map(|e| (Point { x: e[0] as isize, y: e[1] as isize }, e[2]))
+            .collect();
+        let halite_rows = rows(&v["halite_map"], width, &format!("halite_map rows of {}", width))?;
+        if halite_rows.len() != height || width == 0 {
+            return Err(invalid(&format!("a {}x{} halite_map", width, height), v["halite_map"].to_string()));
+        }
+        let halite_map = GMap::from_rows(halite_rows);
+        let energy = nums(&v["energy"])?;
+        if energy.len() != num_players {
+            return Err(invalid(&format!("energy for {} players", num_players), v["energy"].to_string()));
+        }
+
+        let mut game = Game {
+            turn_number: num(&v["turn_number"])?,
+            max_turns: constants.max_turns,
+            constants,
+            num_players,
+            my_pid,
+            factories,
+            width, height,
+            halite_sums: HaliteSums::new(&halite_map),
+            halite_map,
+            ships,
+            ship_id_by_player,
+            ship_map,
+            dropoffs,
+            energy,
+            my_drop_pts,
+            my_drop_field: DistanceField::new(width, height, &[], &unit_cost),
+            home: HomeField::empty(width, height),
+            enemy_drop_pts,
+            inspired: GMap::new(width, height, false),
+            halite_changes: Vec::new(),
+            history: VecDeque::new(),
+        };
+        // snapshots from before the history was kept have none
+        if let Some(changes) = v.get("halite_changes") {
+            game.halite_changes = changes_from_json(changes).map_err(|e| invalid("halite_changes", e))?;
+        }
+        if let Some(history) = v.get("history") {
+            for f in history.as_array().ok_or_else(|| invalid("a list of frames", history.to_string()))? {
+                let frame = Frame::from_json(f).map_err(|e| invalid("a history frame", e))?;
+                if frame.ships.values().any(|s| s.player >= num_players) || frame.dropoffs.iter().any(|d| d.player >= num_players)
+                    || frame.energy.len() != num_players {
+                    return Err(invalid(&format!("a history frame for {} players", num_players), f.to_string()));
+                }
+                game.history.push_back(frame);
+            }
+        }
+        game.update_dropoff_maps();
+        game.update_inspiration_map();
+        game.home = HomeField::new(&game);
+        Ok(game)
+    }
+
 	pub fn ready(&mut self, name: &str) {
-        self.update_dropoff_maps();
         println!("{}", name);
     }
 
-    pub fn update_frame<I: Iterator<Item = String>>(&mut self, lines_iter: &mut I) {
-        self.turn_number = str::parse::<usize>(&lines_iter.next().unwrap()).unwrap() - 1;
+    // Drops lines up to the start of the frame for `turn`, after a frame that could not be
+    // read. The map update count is a lone number too, so a line only counts as the turn
+    // line when the player headers follow it in order, each after the previous player's
+    // ships and dropoffs.
+    pub fn skip_to_frame<I: Iterator<Item = String>>(&self, lines_iter: &mut LineReader<I>, turn: usize)
+        -> Result<(), ParseError> {
+        while !self.frame_starts(lines_iter, turn) {
+            lines_iter.next_line(&format!("the frame for turn {}", turn))?;
+        }
+        Ok(())
+    }
 
-        info!("====== TURN {} ======", self.turn_number);
+    // stops reading ahead at the first line that does not fit
+    fn frame_starts<I: Iterator<Item = String>>(&self, lines_iter: &mut LineReader<I>, turn: usize) -> bool {
+        let mut nums_at = |ahead: usize| -> Option<Vec<usize>> {
+            lines_iter.peek(ahead)?.split_whitespace().map(|n| n.parse::<usize>().ok()).collect()
+        };
+        if nums_at(0) != Some(vec![turn]) {
+            return false;
+        }
+        let mut ahead = 1;
+        for pid in 0..self.num_players {
+            let (num_ships, num_dropoffs) = match nums_at(ahead) {
+                Some(ref header) if header.len() == 4 && header[0] == pid => (header[1], header[2]),
+                _ => return false,
+            };
+            if num_ships + num_dropoffs > 2*self.width*self.height {
+                return false;
+            }
+            ahead += 1;
+            for i in 0..(num_ships + num_dropoffs) {
+                let len = if i < num_ships { 4 } else { 3 };
+                if nums_at(ahead).map(|line| line.len()) != Some(len) {
+                    return false;
+                }
+                ahead += 1;
+            }
+        }
+        true
+    }
 
-        self.ship_id_by_player.clear();
-        self.ships.clear();
-        self.dropoffs.clear();
-        self.ship_map.clear();
-        for _ in 0..self.num_players {
-            let player_info = parse_line_of_nums(lines_iter);
+    // Reads the next frame without changing anything, so that a frame that fails to parse
+    // leaves the game as it was after the last good one.
+    fn read_frame<I: Iterator<Item = String>>(&self, lines_iter: &mut LineReader<I>) -> Result<FrameInput, ParseError> {
+        let turn = parse_line_of_nums(lines_iter, "turn number", 1)?[0];
+        if turn == 0 {
+            return Err(lines_iter.error("turn number starting at 1", "0"));
+        }
+
+        let mut frame = FrameInput {
+            turn,
+            energy: vec![0; self.num_players],
+            ships: Vec::new(),
+            dropoffs: Vec::new(),
+            map_updates: Vec::new(),
+        };
+        for expected_id in 0..self.num_players {
+            let player_info = parse_line_of_nums(lines_iter, "player \"id ships dropoffs halite\"", 4)?;
             let player_id = player_info[0];
+            if player_id != expected_id {
+                return Err(lines_iter.error("players in id order", &format!("{:?}", player_info)));
+            }
             let num_ships = player_info[1];
             let num_dropoffs = player_info[2];
-            self.energy[player_id] = player_info[3];
+            frame.energy[player_id] = player_info[3];
 
             // info!("player_info: {:?}", player_info);
-            self.ship_id_by_player.push(Vec::new());
             info!("player {} info: {:?}", player_id, player_info);
             for _ in 0..num_ships {
-                let ship_info = parse_line_of_nums(lines_iter);
-                let ship_id = ship_info[0];
-                let x = ship_info[1] as isize;
-                let y = ship_info[2] as isize;
-                self.ship_id_by_player[player_id].push(ship_id);
-                let s = Ship {
+                let ship_info = parse_line_of_nums(lines_iter, "ship \"id x y halite\"", 4)?;
+                if ship_info[1] >= self.width || ship_info[2] >= self.height {
+                    return Err(lines_iter.error("ship inside the map", &format!("{:?}", ship_info)));
+                }
+                frame.ships.push(Ship {
                     player: player_id,
-                    ship_id,
-                    pos: Point{x, y,},
+                    ship_id: ship_info[0],
+                    pos: Point{x: ship_info[1] as isize, y: ship_info[2] as isize,},
                     halite: ship_info[3] as isize,
-                };
-                self.ships.insert(ship_id, s);
-                self.ship_map.insert(Point{x, y}, ship_id);
+                });
             }
 
             for _ in 0..num_dropoffs {
-                let dropoff_info = parse_line_of_nums(lines_iter);
-                let d_pos = Point {
+                let dropoff_info = parse_line_of_nums(lines_iter, "dropoff \"id x y\"", 3)?;
+                if dropoff_info[1] >= self.width || dropoff_info[2] >= self.height {
+                    return Err(lines_iter.error("dropoff inside the map", &format!("{:?}", dropoff_info)));
+                }
+                frame.dropoffs.push(Dropoff {
+                    player: player_id,
+                    pos: Point {
                         x: dropoff_info[1] as isize,
                         y: dropoff_info[2] as isize,
-                    };
-                self.dropoffs.push(Dropoff {
-                    player: player_id,
-                    pos: d_pos,
+                    },
                 });
-                if self.my_pid == player_id {
-                    if !self.my_drop_pts.contains(&d_pos) {
-                        self.my_drop_pts.push(d_pos);
-                        self.update_dropoff_maps();
-                    }
-                } else {
-                    if !self.enemy_drop_pts.contains_key(&d_pos) {
-                        self.enemy_drop_pts.insert(d_pos, player_id);
-                    }
-                }
             }
         }
 
-        let num_map_updates = str::parse::<usize>(&lines_iter.next().unwrap()).unwrap();
+        let num_map_updates = parse_line_of_nums(lines_iter, "number of map updates", 1)?[0];
         for _ in 0..num_map_updates {
-            let map_update = parse_line_of_nums(lines_iter);
+            let map_update = parse_line_of_nums(lines_iter, "map update \"x y halite\"", 3)?;
             let x = map_update[0];
             let y = map_update[1];
-            self.halite_map.gmap[y][x] = map_update[2];
+            if x >= self.width || y >= self.height {
+                return Err(lines_iter.error("map update inside the map", &format!("{:?}", map_update)));
+            }
+            frame.map_updates.push((Point { x: x as isize, y: y as isize }, map_update[2]));
+        }
+        Ok(frame)
+    }
+
+    pub fn update_frame<I: Iterator<Item = String>>(&mut self, lines_iter: &mut LineReader<I>) -> Result<(), ParseError> {
+        let frame = self.read_frame(lines_iter)?;
+
+        // a Game fresh from init has not read a frame yet, so has nothing to keep
+        if !self.ship_id_by_player.is_empty() {
+            if self.history.len() == HISTORY_LEN {
+                self.history.pop_front();
+            }
+            self.history.push_back(Frame {
+                turn_number: self.turn_number,
+                ships: mem::take(&mut self.ships),
+                dropoffs: mem::take(&mut self.dropoffs),
+                energy: self.energy.clone(),
+                halite_changes: mem::take(&mut self.halite_changes),
+            });
+        }
+        self.turn_number = frame.turn - 1;
+
+        info!("====== TURN {} ======", self.turn_number);
+
+        self.energy = frame.energy;
+        self.ship_id_by_player = vec![Vec::new(); self.num_players];
+        self.ships.clear();
+        self.dropoffs.clear();
+        self.ship_map.clear();
+        for s in frame.ships {
+            self.ship_id_by_player[s.player].push(s.ship_id);
+            self.ship_map.insert(s.pos, s.ship_id);
+            self.ships.insert(s.ship_id, s);
+        }
+
+        for d in frame.dropoffs {
+            if self.my_pid == d.player {
+                if !self.my_drop_pts.contains(&d.pos) {
+                    self.my_drop_pts.push(d.pos);
+                    self.my_drop_field.add_source(d.pos, &unit_cost);
+                }
+            } else {
+                self.enemy_drop_pts.entry(d.pos).or_insert(d.player);
+            }
+            self.dropoffs.push(d);
+        }
+
+        for (pos, halite) in frame.map_updates {
+            self.halite_changes.push((pos, halite as isize - self.halite_map[pos] as isize));
+            *self.halite_map.get_mut(pos) = halite;
+            self.halite_sums.set(pos, halite);
         }
+        self.halite_sums.refresh();
 
         // info!("ships: \n{:?}\nship_id_by_player: \n{:?}\ndropoffs: {:?}\nnum map updates: {}\n",
         //     self.ships, self.ship_id_by_player, self.dropoffs, num_map_updates);
+        self.update_inspiration_map();
+        self.home = HomeField::new(self);
+        Ok(())
     }
 
-    pub fn end_turn((spawn, ship_commands): (bool, HashMap<usize, ShipCommand>)) {
+    pub fn end_turn((spawn, ship_commands): TurnCommands) {
         if spawn {
             print!("g ");
         }
@@ -209,63 +758,78 @@ impl Game {
     }
 
     pub fn update_dropoff_maps(&mut self) {
-        let vmaps = self.make_vmaps(&self.my_drop_pts);
-        self.nearest_drop_pt_idx = vmaps.0;
-        self.nearest_drop_pt_dist = vmaps.1;
+        self.my_drop_field = DistanceField::new(self.width, self.height, &self.my_drop_pts, &unit_cost);
 
         // f-log contour map of distance
         // let color_str = vec!["#000010", "#000030", "#000050", "#000070", "#000090", "#0000B0", "#0000D0"];
         // for x in 0..self.width {
         //     for y in 0..self.height {
-        //         let d = self.nearest_drop_pt_dist.gmap[y][x];
+        //         let d = *self.my_drop_field.dist.get_idx(x, y);
         //         warn!("{{\"t\": {}, \"x\": {}, \"y\": {}, \"msg\": \"dist {} dropoff {}\", \"color\": \"{}\"}},",
-        //         self.turn_number, x, y, d, self.nearest_drop_pt_idx.gmap[y][x],
+        //         self.turn_number, x, y, d, self.my_drop_field.nearest.get_idx(x, y),
         //         color_str[d%color_str.len()]);
         //     }
         // }
     }
 
-    // returns (index, distance)
-    pub fn make_vmaps(&self, pts: &Vec<Point>) -> (GMap<usize>, GMap<usize>) {
-        // assume width == height
-        let nd = pts.len();
-        assert!(nd > 0);
-        if nd == 1 {
-            let nearest_pt_idx = GMap{ gmap: vec![vec![0; self.width]; self.height] };
-            let mut nearest_pt_dist = GMap{ gmap: vec![vec![0; self.width]; self.height] };
-            for d in 0..(self.width+1) {
-                for p in self.tiles_at_dist(pts[0], d) {
-                    *nearest_pt_dist.get_mut(p) = d;
+    // counts the enemy ships within the inspiration radius of every tile
+    pub fn update_inspiration_map(&mut self) {
+        let mut counts = GMap::new(self.width, self.height, 0);
+        if self.constants.inspiration_enabled {
+            for (pid, ids) in self.ship_id_by_player.iter().enumerate() {
+                if pid == self.my_pid {
+                    continue;
                 }
-            }
-            (nearest_pt_idx, nearest_pt_dist)
-        } else {
-            let mut open = vec![true; nd];
-            let mut nearest_pt_idx = GMap{ gmap: vec![vec![self.width*self.height+1; self.width]; self.height] };
-            let mut nearest_pt_dist = GMap{ gmap: vec![vec![self.width+self.height+1; self.width]; self.height] };
-            for d in 0..(self.width+1) {
-                for d_idx in 0..nd {
-                    if open[d_idx] {
-                        let mut update = false;
-                        for p in self.tiles_at_dist(pts[d_idx], d) {
-                            if *nearest_pt_idx.get(p) >= self.width*self.height+1 {
-                                *nearest_pt_idx.get_mut(p) = d_idx;
-                                *nearest_pt_dist.get_mut(p) = d;
-                                update = true;
-                            }
-                        }
-                        open[d_idx] = update;
+                for id in ids {
+                    for p in self.tiles_within_dist(self.ships[id].pos, self.constants.inspiration_radius) {
+                        *counts.get_mut(p) += 1;
                     }
                 }
             }
-            (nearest_pt_idx, nearest_pt_dist)
-        }   
+        }
+        let needed = self.constants.inspiration_ship_count;
+        self.inspired = counts.map(|&c| c >= needed);
+    }
+
+    // the shipyard and dropoffs of any player, mine included
+    pub fn drop_pts_of(&self, player: usize) -> Vec<Point> {
+        self.factories.iter().filter(|f| f.player == player).map(|f| f.pos)
+            .chain(self.dropoffs.iter().filter(|d| d.player == player).map(|d| d.pos))
+            .collect()
+    }
+
+    // the ship in each of up to n frames before this one, latest first, stopping at the
+    // first frame it was not in
+    pub fn ship_history(&self, id: usize, n: usize) -> Vec<Ship> {
+        self.history.iter().rev().take(n).map_while(|f| f.ships.get(&id).cloned()).collect()
+    }
+
+    // the player's halite in every kept frame and this one, oldest first
+    pub fn energy_history(&self, player: usize) -> Vec<usize> {
+        self.history.iter().map(|f| f.energy[player]).chain(Some(self.energy[player])).collect()
+    }
+
+    // how much the halite of every tile that changed has gone up or down since the frame
+    // of turn, or None if that frame is not kept
+    pub fn halite_delta_since(&self, turn: usize) -> Option<HashMap<Point, isize>> {
+        let oldest = self.history.front().map_or(self.turn_number, |f| f.turn_number);
+        if turn < oldest || turn > self.turn_number {
+            return None;
+        }
+        let later_frames = self.history.iter()
+            .filter(|f| f.turn_number > turn)
+            .map(|f| &f.halite_changes)
+            .chain(if self.turn_number > turn { Some(&self.halite_changes) } else { None });
+        let mut delta: HashMap<Point, isize> = HashMap::new();
+        for &(pos, change) in later_frames.flatten() {
+            *delta.entry(pos).or_insert(0) += change;
+        }
+        delta.retain(|_, d| *d != 0);
+        Some(delta)
     }
 
     pub fn nearest_drop_pos(&self, pos: Point) -> Point{
-        let idx = *self.nearest_drop_pt_idx.get(pos);
-        assert!(idx < self.my_drop_pts.len());
-        self.my_drop_pts[idx]
+        self.my_drop_field.nearest_source(pos).unwrap()
     }
 
     pub fn normalize(&self, pos: Point) -> Point {
@@ -284,63 +848,65 @@ impl Game {
         *self.halite_map.get(pos)
     }
 
+    // whether the engine treats ship as inspired this turn, which depends on the ships of
+    // every other player; mine are looked up in the inspiration map
+    pub fn is_inspired(&self, ship: &Ship) -> bool {
+        if ship.player == self.my_pid {
+            return *self.inspired.get(ship.pos);
+        }
+        self.constants.inspiration_enabled && self.ships.values()
+            .filter(|o| o.player != ship.player && self.dist(o.pos, ship.pos) <= self.constants.inspiration_radius)
+            .count() >= self.constants.inspiration_ship_count
+    }
+
+    // halite ship burns if it leaves its tile this turn
+    pub fn move_cost_of(&self, ship: &Ship) -> usize {
+        self.constants.move_cost(self.halite_at(ship.pos), self.is_inspired(ship))
+    }
+
+    // halite on a tile weighted by how much faster an inspired ship takes it home,
+    // relative to an uninspired one
+    pub fn mining_value(&self, pos: Point) -> f32 {
+        let halite = self.halite_at(pos) as f32;
+        if *self.inspired.get(pos) {
+            let c = &self.constants;
+            halite*(1.0 + c.inspired_bonus_multiplier as f32)*c.extract_ratio as f32/c.inspired_extract_ratio as f32
+        } else {
+            halite
+        }
+    }
+
     pub fn dist(&self, pos1: Point, pos2: Point) -> usize {
-        let dx = (pos1.x - pos2.x).abs() as usize;
-        let dy = (pos1.y - pos2.y).abs() as usize;
+        let dx = (pos1.x - pos2.x).unsigned_abs();
+        let dy = (pos1.y - pos2.y).unsigned_abs();
         let toroidal_dx = min(dx, self.width-dx);
         let toroidal_dy = min(dy, self.height-dy);
         toroidal_dx + toroidal_dy
     }
 
+    // the largest distance between two tiles
+    pub fn max_dist(&self) -> usize {
+        self.width/2 + self.height/2
+    }
+
     pub fn tiles_at_dist(&self, pos: Point, dist: usize) -> Vec<Point> {
-        // assumes width == height and both are even
         if dist == 0 {
-            vec![pos]
-        } else if dist < self.width/2 {
-            (0..(dist as isize)).map(|d|
-                self.normalize(Point{
-                    x: pos.x + (dist as isize) - d, 
-                    y: pos.y + d}))
-            .chain((0..(dist as isize)).map(|d|
-                self.normalize(Point{
-                    x: pos.x - d, 
-                    y: pos.y + (dist as isize) - d})))
-            .chain((0..(dist as isize)).map(|d|
-                self.normalize(Point{
-                    x: pos.x - (dist as isize) + d, 
-                    y: pos.y - d})))
-            .chain((0..(dist as isize)).map(|d|
-                self.normalize(Point{
-                    x: pos.x + d, 
-                    y: pos.y - (dist as isize) + d})))
-            .collect()
-        } else if dist == self.width/2 {
-            (0..(dist as isize)).map(|d|
-                self.normalize(Point{
-                    x: pos.x + (dist as isize) - d, 
-                    y: pos.y + d}))
-            .chain((0..(dist as isize)).map(|d|
-                self.normalize(Point{
-                    x: pos.x - d, 
-                    y: pos.y + (dist as isize) - d})))
-            .chain((1..(dist as isize)).map(|d|
-                self.normalize(Point{
-                    x: pos.x - (dist as isize) + d, 
-                    y: pos.y - d})))
-            .chain((1..(dist as isize)).map(|d|
-                self.normalize(Point{
-                    x: pos.x + d, 
-                    y: pos.y - (dist as isize) + d})))
-            .collect()
-        } else if dist <= self.width {
-            let antipode = self.normalize(Point {
-                x: pos.x + (self.width as isize)/2,
-                y: pos.y + (self.height as isize)/2,
-            });
-            self.tiles_at_dist(antipode, self.width - dist)
-        } else {
-            vec![]
+            return vec![pos];
+        } else if dist > self.max_dist() {
+            return vec![];
         }
+        // Walk the diamond |dx| + |dy| = dist, keeping an offset only if it is the shortest
+        // way to its tile (-width < 2*dx <= width, the same for dy). On small maps or at large
+        // distances the diamond wraps onto itself, and this keeps each tile once.
+        let (w, h) = (self.width as isize, self.height as isize);
+        let r = dist as isize;
+        (0..r).map(|d| (r - d, d))
+            .chain((0..r).map(|d| (-d, r - d)))
+            .chain((0..r).map(|d| (-r + d, -d)))
+            .chain((0..r).map(|d| (d, -r + d)))
+            .filter(|&(dx, dy)| -w < 2*dx && 2*dx <= w && -h < 2*dy && 2*dy <= h)
+            .map(|(dx, dy)| self.normalize(Point { x: pos.x + dx, y: pos.y + dy }))
+            .collect()
     }
 
     pub fn tiles_within_dist(&self, pos: Point, dist: usize) -> Vec<Point> {
@@ -359,8 +925,16 @@ impl Game {
         tiles
     }
 
+    // the same for every tile, len() of tiles_within_dist
     pub fn num_tiles_within_dist(&self, dist: usize) -> usize {
-        2*dist*(dist+1)+1
+        let w = self.width as isize;
+        ((-(w-1)/2)..(w/2+1))
+            .filter(|dx| dx.unsigned_abs() <= dist)
+            .map(|dx| {
+                let r = dist - dx.unsigned_abs();
+                if 2*r < self.height { 2*r+1 } else { self.height }
+            })
+            .sum()
     }
 
     pub fn step_toward(&self, pos: Point, d: Direction) -> Point {
@@ -442,17 +1016,17 @@ impl Game {
         let no_dx = start.x == goal.x;
         let no_dy = start.y == goal.y;
         if start.x > goal.x {
-            rightwards = start.x-goal.x > width/2;
+            rightwards = 2*(start.x-goal.x) > width;
             x_wrap = rightwards;
         } else {
-            rightwards = goal.x-start.x < width/2;
+            rightwards = 2*(goal.x-start.x) < width;
             x_wrap = !rightwards;
         }
         if start.y > goal.y {
-            downwards = start.y-goal.y > height/2;
+            downwards = 2*(start.y-goal.y) > height;
             y_wrap = downwards;
         } else {
-            downwards = goal.y-start.y < height/2;
+            downwards = 2*(goal.y-start.y) < height;
             y_wrap = !downwards;
         }
 
@@ -460,33 +1034,30 @@ impl Game {
 
         if no_dy {
             sum = if x_wrap {
-                self.halite_map.gmap[y1][x2..self.width]
+                self.halite_map.row(y1)[x2..self.width]
                     .iter()
                     .map(h_fn)
                     .sum::<usize>() as usize +
-                self.halite_map.gmap[y1][0..(x1+1)]
+                self.halite_map.row(y1)[0..(x1+1)]
                     .iter()
                     .map(h_fn)
                     .sum::<usize>() as usize
             } else {
-                self.halite_map.gmap[y1][x1..(x2+1)]
+                self.halite_map.row(y1)[x1..(x2+1)]
                     .iter()
                     .sum::<usize>() as usize
             };
         } else if no_dx {
             sum = if y_wrap {
-                self.halite_map.gmap[y2..self.height]
-                    .iter()
-                    .map(|s| h_fn(&s[x1]))
+                (y2..self.height)
+                    .map(|y| h_fn(self.halite_map.get_idx(x1, y)))
                     .sum::<usize>() as usize +
-                self.halite_map.gmap[0..(y1+1)]
-                    .iter()
-                    .map(|s| h_fn(&s[x1]))
+                (0..(y1+1))
+                    .map(|y| h_fn(self.halite_map.get_idx(x1, y)))
                     .sum::<usize>() as usize
             } else {
-                self.halite_map.gmap[y1..(y2+1)]
-                    .iter()
-                    .map(|s| h_fn(&s[x1]))
+                (y1..(y2+1))
+                    .map(|y| h_fn(self.halite_map.get_idx(x1, y)))
                     .sum::<usize>() as usize
             };   
         } else {
@@ -496,16 +1067,16 @@ impl Game {
             assert_eq!(y_idx.len(), y_len);
 
             let mut sum_so_far = vec![vec![0; x_len]; y_len];
-            sum_so_far[0][0] = h_fn(&self.halite_map.gmap[y_idx[0]][x_idx[0]]);
+            sum_so_far[0][0] = h_fn(self.halite_map.get_idx(x_idx[0], y_idx[0]));
             for xi in 1..x_len {
-                sum_so_far[0][xi] = sum_so_far[0][xi-1] + h_fn(&self.halite_map.gmap[y_idx[0]][x_idx[xi]]);
+                sum_so_far[0][xi] = sum_so_far[0][xi-1] + h_fn(self.halite_map.get_idx(x_idx[xi], y_idx[0]));
             }
             for yi in 1..y_len {
-                sum_so_far[yi][0] = sum_so_far[yi-1][0] + h_fn(&self.halite_map.gmap[y_idx[yi]][x_idx[0]]);
+                sum_so_far[yi][0] = sum_so_far[yi-1][0] + h_fn(self.halite_map.get_idx(x_idx[0], y_idx[yi]));
             }
             for xi in 1..x_len {
                 for yi in 1..y_len {
-                    sum_so_far[yi][xi] = h_fn(&self.halite_map.gmap[y_idx[yi]][x_idx[xi]]) +
+                    sum_so_far[yi][xi] = h_fn(self.halite_map.get_idx(x_idx[xi], y_idx[yi])) +
                         min(sum_so_far[yi][xi-1], sum_so_far[yi-1][xi]);
                 }
             }
@@ -513,168 +1084,6 @@ impl Game {
         }
         sum - h_fn(&self.halite_at(start)) - h_fn(&self.halite_at(goal))
     }
-
-    pub fn halite_between(&self, start: Point, goal: Point) -> usize {
-        if start == goal {
-            return 0;
-        }
-        let k: isize = 100000;
-        let mut frontier = BinaryHeap::new(); // open set
-        let mut explored = HashSet::new(); // closed set
-        frontier.push(CellPriority{ pos: start, w: 0 }); // start nav from target square
-
-        let mut came_from: HashMap<Point, Option<Point>> = HashMap::new();
-        let mut cost_so_far: HashMap<Point, isize> = HashMap::new();
-        came_from.insert(start, None);
-        cost_so_far.insert(start, 0);
-
-        while !frontier.is_empty() {
-            let current = frontier.pop().unwrap().pos;
-
-            // terminate when have assigned priorities to every square in the neighborhood
-            if current == goal {
-                break;
-            }
-
-            if !explored.contains(&current) {
-                for next in self.neighbors(current) {
-                    let new_cost = cost_so_far.get(&current).unwrap() + k + self.halite_at(next) as isize;
-                    if !cost_so_far.contains_key(&next) || new_cost < *cost_so_far.get(&next).unwrap() {
-                        cost_so_far.insert(next, new_cost);
-                        let cost_to_goal = k*self.dist(start, next) as isize;
-                        let total_cost = new_cost + cost_to_goal;
-                        frontier.push(CellPriority{ pos: next, w: total_cost });
-                            // RIP - problem: what if already exists CellPriority { pos: next, w: different_cost } ?
-                        came_from.insert(next, Some(current));
-                    }
-                }
-
-                explored.insert(current);
-            } else {
-                // info!("nav from {:?} to {:?}, {:?} already in closed set", start, target, current); // this happens quite frequently
-            }
-        }
-
-        let mut sum = 0;
-        let mut node = goal;
-        loop {
-            node = came_from.get(&node).unwrap().unwrap();
-            if node == start {
-                break;
-            }
-            sum += self.halite_at(node);
-        }
-        sum
-    }
-
-        // figure which of the five squares in the neighborhood of start to go to
-    pub fn backwards_a_star_scores(&self, start: Point, target: Point, k: isize) -> Vec<(Point, Direction, isize)> {
-        let mut frontier = BinaryHeap::new(); // open set
-        let mut explored = HashSet::new(); // closed set
-        frontier.push(CellPriority{ pos: target, w: 0 }); // start nav from target square
-
-        let mut came_from: HashMap<Point, Option<Point>> = HashMap::new();
-        let mut cost_so_far: HashMap<Point, isize> = HashMap::new();
-        came_from.insert(target, None);
-        cost_so_far.insert(target, 0);
-
-        let start_neighborhood0 = Vec::from_iter(self.neighborhood(start));
-        let mut start_neighborhood: HashSet<Point> = HashSet::from_iter(self.neighborhood(start));
-
-        while !frontier.is_empty() && !start_neighborhood.is_empty() {
-            let current = frontier.pop().unwrap().pos;
-
-            // terminate when have assigned priorities to every square in the neighborhood
-
-            if !explored.contains(&current) {
-                for next in self.neighbors(current) {
-                    let new_cost = cost_so_far.get(&current).unwrap() + k + self.halite_at(next) as isize;
-                    if !cost_so_far.contains_key(&next) || new_cost < *cost_so_far.get(&next).unwrap() {
-                        cost_so_far.insert(next, new_cost);
-                        let cost_to_goal = k*self.dist(start, next) as isize;
-                        let total_cost = new_cost + cost_to_goal;
-                        frontier.push(CellPriority{ pos: next, w: total_cost });
-                            // RIP - problem: what if already exists CellPriority { pos: next, w: different_cost } ?
-                        came_from.insert(next, Some(current));
-                    }
-                }
-
-                explored.insert(current);
-                start_neighborhood.remove(&current);
-            } else {
-                // info!("nav from {:?} to {:?}, {:?} already in closed set", start, target, current); // this happens quite frequently
-            }
-        }
-
-        let mut scores = Vec::new();
-        for m in 0..5 {
-            scores.push((start_neighborhood0[m], Direction::all_directions()[m],
-                *cost_so_far.get(&start_neighborhood0[m]).unwrap()));
-        }
-
-        return scores;
-    }
-
-    // old
-    // figure which of the five squares in the neighborhood of start to go to
-    pub fn backwards_a_star(&self, start: Point, target: Point, forbidden: &HashSet<Point>) -> (Point, Direction) {
-        let k: isize = 100000;
-        let mut frontier = BinaryHeap::new(); // open set
-        let mut explored = HashSet::new(); // closed set
-        frontier.push(CellPriority{ pos: target, w: 0 }); // start nav from target square
-
-        let mut came_from: HashMap<Point, Option<Point>> = HashMap::new();
-        let mut cost_so_far: HashMap<Point, isize> = HashMap::new();
-        came_from.insert(target, None);
-        cost_so_far.insert(target, 0);
-
-        let mut start_neighborhood: HashSet<Point> = HashSet::from_iter(self.neighborhood(start));
-
-        while !frontier.is_empty() && !start_neighborhood.is_empty() {
-            let current = frontier.pop().unwrap().pos;
-
-            // terminate when have assigned priorities to every square in the neighborhood
-
-            if !explored.contains(&current) {
-                for next in self.neighbors(current) {
-                    let new_cost = cost_so_far.get(&current).unwrap() + k + self.halite_at(next) as isize;
-                    if !cost_so_far.contains_key(&next) || new_cost < *cost_so_far.get(&next).unwrap() {
-                        cost_so_far.insert(next, new_cost);
-                        let cost_to_goal = k*self.dist(start, next) as isize;
-                        let total_cost = new_cost + cost_to_goal;
-                        frontier.push(CellPriority{ pos: next, w: total_cost });
-                            // RIP - problem: what if already exists CellPriority { pos: next, w: different_cost } ?
-                        came_from.insert(next, Some(current));
-                    }
-                }
-
-                explored.insert(current);
-                start_neighborhood.remove(&current);
-            } else {
-                // info!("nav from {:?} to {:?}, {:?} already in closed set", start, target, current); // this happens quite frequently
-            }
-        }
-
-        let mut best_score = isize::MAX;
-        let mut best_direction = Direction::Still;
-        let mut best_pos = start;
-        for d in Direction::all_directions() {
-            let new_pos = self.step_toward(start, d);
-            let mut score = *cost_so_far.get(&new_pos).unwrap();
-            if forbidden.contains(&new_pos) {
-                score += k*1000;
-            }
-            if start == target {
-                score -= 4*self.halite_at(new_pos) as isize;
-            }
-            if score < best_score {
-                best_score = score;
-                best_direction = d;
-                best_pos = new_pos;
-            }
-        }
-        (best_pos, best_direction)
-    }
 }
 
 pub fn get_wrap_idx(c1: usize, c2: usize, wrap: bool, reverse: bool, max_dim: usize) -> Vec<usize> {
@@ -691,41 +1100,110 @@ pub fn get_wrap_idx(c1: usize, c2: usize, wrap: bool, reverse: bool, max_dim: us
     idx
 }
 
-#[derive(Debug)]
+// A value for every tile, stored row after row. Points are wrapped onto the map,
+// so neighbors of edge tiles can be looked up without normalizing first.
+#[derive(Debug, Clone, PartialEq)]
 pub struct GMap<T> {
-    pub gmap: Vec<Vec<T>>,
+    pub width: usize,
+    pub height: usize,
+    cells: Vec<T>,
 }
 
 impl <T> GMap<T> {
+    pub fn new(width: usize, height: usize, value: T) -> GMap<T> where T: Clone {
+        GMap { width, height, cells: vec![value; width*height] }
+    }
+
+    pub fn from_fn<F: FnMut(Point) -> T>(width: usize, height: usize, mut f: F) -> GMap<T> {
+        let mut cells = Vec::with_capacity(width*height);
+        for y in 0..height {
+            for x in 0..width {
+                cells.push(f(Point { x: x as isize, y: y as isize }));
+            }
+        }
+        GMap { width, height, cells }
+    }
+
+    // rows[y][x]; every row must be as long as the first
+    pub fn from_rows(rows: Vec<Vec<T>>) -> GMap<T> {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |r| r.len());
+        assert!(rows.iter().all(|r| r.len() == width), "GMap rows of different lengths");
+        GMap { width, height, cells: rows.into_iter().flat_map(|r| r.into_iter()).collect() }
+    }
+
+    pub fn to_rows(&self) -> Vec<Vec<T>> where T: Clone {
+        self.rows().map(|r| r.to_vec()).collect()
+    }
+
+    fn index_of(&self, pos: Point) -> usize {
+        let x = pos.x.rem_euclid(self.width as isize) as usize;
+        let y = pos.y.rem_euclid(self.height as isize) as usize;
+        y*self.width + x
+    }
+
     pub fn get(&self, pos: Point) -> &T {
-        &self.gmap[pos.y as usize][pos.x as usize]
+        &self.cells[self.index_of(pos)]
     }
 
     pub fn get_idx(&self, x: usize, y: usize) -> &T {
-        &self.gmap[y][x]
+        &self.cells[y*self.width + x]
     }
 
     pub fn get_mut(&mut self, pos: Point) -> &mut T {
-        &mut self.gmap[pos.y as usize][pos.x as usize]
+        let idx = self.index_of(pos);
+        &mut self.cells[idx]
     }
-}
 
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y*self.width..(y+1)*self.width]
+    }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct CellPriority {
-    pub pos: Point,
-    pub w: isize,
+    pub fn rows(&self) -> impl Iterator<Item=&[T]> {
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn column(&self, x: usize) -> impl Iterator<Item=&T> {
+        assert!(x < self.width);
+        self.cells.iter().skip(x).step_by(self.width)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&T> {
+        self.cells.iter()
+    }
+
+    // every tile with its value, row by row
+    pub fn points(&self) -> impl Iterator<Item=(Point, &T)> {
+        let width = self.width;
+        self.cells.iter().enumerate()
+            .map(move |(i, v)| (Point { x: (i % width) as isize, y: (i / width) as isize }, v))
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> GMap<U> {
+        GMap { width: self.width, height: self.height, cells: self.cells.iter().map(f).collect() }
+    }
+
+    pub fn zip_with<U, V, F: FnMut(&T, &U) -> V>(&self, other: &GMap<U>, mut f: F) -> GMap<V> {
+        assert!(self.width == other.width && self.height == other.height);
+        GMap {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().zip(other.cells.iter()).map(|(a, b)| f(a, b)).collect(),
+        }
+    }
 }
 
-impl Ord for CellPriority {
-    fn cmp(&self, other: &CellPriority) -> Ordering {
-        other.w.cmp(&self.w) // so that smaller numbers go first
+impl <T> Index<Point> for GMap<T> {
+    type Output = T;
+
+    fn index(&self, pos: Point) -> &T {
+        self.get(pos)
     }
 }
 
-impl PartialOrd for CellPriority {
-    fn partial_cmp(&self, other: &CellPriority) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl <T> IndexMut<Point> for GMap<T> {
+    fn index_mut(&mut self, pos: Point) -> &mut T {
+        self.get_mut(pos)
     }
 }
 
@@ -745,4 +1223,208 @@ impl PartialOrd for CellPriorityMax {
     fn partial_cmp(&self, other: &CellPriorityMax) -> Option<Ordering> {
         Some(self.cmp(other))
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_SIZE: usize = 12;
+
+    // a one-player game on an empty width x height map
+    fn blank_game(width: usize, height: usize) -> Game {
+        let mut lines = vec!["0 0 0".to_owned(), format!("{} {}", width, height)];
+        lines.extend((0..height).map(|_| vec!["0"; width].join(" ")));
+        Game::init(&mut LineReader::new(lines.into_iter()), Constants::defaults(width, height).to_json(), 1, 0)
+            .unwrap()
+    }
+
+    fn all_tiles(game: &Game) -> Vec<Point> {
+        (0..game.height)
+            .flat_map(|y| (0..game.width).map(move |x| Point { x: x as isize, y: y as isize }))
+            .collect()
+    }
+
+    // the tiles in reading order, failing on any tile listed twice
+    fn sorted(mut tiles: Vec<Point>) -> Vec<Point> {
+        let n = tiles.len();
+        tiles.sort_by_key(|p| (p.y, p.x));
+        tiles.dedup();
+        assert_eq!(tiles.len(), n, "a tile is listed more than once");
+        tiles
+    }
+
+    // every tile with its distance from pos
+    fn dists_from(game: &Game, pos: Point) -> Vec<(Point, usize)> {
+        all_tiles(game).into_iter().map(|p| (p, game.dist(pos, p))).collect()
+    }
+
+    fn brute_force(dists: &[(Point, usize)], within: &dyn Fn(usize) -> bool) -> Vec<Point> {
+        dists.iter().filter(|&&(_, d)| within(d)).map(|&(p, _)| p).collect()
+    }
+
+    fn every_size() -> Vec<Game> {
+        (1..(MAX_SIZE+1))
+            .flat_map(|w| (1..(MAX_SIZE+1)).map(move |h| (w, h)))
+            .map(|(w, h)| blank_game(w, h))
+            .collect()
+    }
+
+    #[test]
+    fn max_dist_is_the_largest_dist() {
+        for game in every_size() {
+            let origin = Point { x: 0, y: 0 };
+            let largest = all_tiles(&game).into_iter().map(|p| game.dist(origin, p)).max().unwrap();
+            assert_eq!(game.max_dist(), largest, "{}x{}", game.width, game.height);
+        }
+    }
+
+    #[test]
+    fn tiles_at_dist_matches_brute_force() {
+        for game in every_size() {
+            for pos in all_tiles(&game) {
+                let dists = dists_from(&game, pos);
+                for d in 0..(game.max_dist()+3) {
+                    assert_eq!(sorted(game.tiles_at_dist(pos, d)), brute_force(&dists, &|e| e == d),
+                        "{}x{} at ({}, {}), dist {}", game.width, game.height, pos.x, pos.y, d);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tiles_within_dist_matches_brute_force() {
+        for game in every_size() {
+            for pos in all_tiles(&game) {
+                let dists = dists_from(&game, pos);
+                for d in 0..(game.max_dist()+3) {
+                    let tiles = sorted(game.tiles_within_dist(pos, d));
+                    assert_eq!(tiles, brute_force(&dists, &|e| e <= d),
+                        "{}x{} at ({}, {}), dist {}", game.width, game.height, pos.x, pos.y, d);
+                    assert_eq!(game.num_tiles_within_dist(d), tiles.len(),
+                        "{}x{}, dist {}", game.width, game.height, d);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tiles_between_dist_matches_brute_force() {
+        for game in every_size() {
+            // made of tiles_at_dist, already checked from every tile, so a few starts will do
+            let (w, h) = (game.width as isize, game.height as isize);
+            for &pos in &[Point { x: 0, y: 0 }, Point { x: w/2, y: h/2 }, Point { x: w-1, y: h-1 }] {
+                let dists = dists_from(&game, pos);
+                for d1 in 0..(game.max_dist()+2) {
+                    for d2 in d1..(game.max_dist()+2) {
+                        assert_eq!(sorted(game.tiles_between_dist(pos, d1, d2)), brute_force(&dists, &|e| d1 <= e && e <= d2),
+                            "{}x{} at ({}, {}), dist {} to {}", game.width, game.height, pos.x, pos.y, d1, d2);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn from_json_reads_back_to_json() {
+        let mut game = blank_game(5, 4);
+        game.ships.insert(3, Ship { player: 0, ship_id: 3, pos: Point { x: 4, y: 1 }, halite: 120 });
+        game.ship_id_by_player = vec![vec![3]];
+        game.ship_map.insert(Point { x: 4, y: 1 }, 3);
+        let read = Game::from_json(&game.to_json()).unwrap();
+        assert_eq!(read.to_json(), game.to_json());
+    }
+
+    #[test]
+    fn from_json_rejects_out_of_range_snapshots() {
+        let good = blank_game(5, 4).to_json();
+        assert!(Game::from_json(&good).is_ok());
+        let bad: Vec<(&str, Value)> = vec![
+            ("my_pid", json!(1)),
+            ("factories", json!([[1, 0, 0]])),
+            ("factories", json!([[0, 0]])),
+            ("ships", json!([[3, 1, 0, 0, 0]])),
+            ("ship_map", json!([[0, 0, 3]])),
+            ("dropoffs", json!([[2, 1, 1]])),
+            ("enemy_drop_pts", json!([[1, 1, 4]])),
+            ("halite_map", json!([[0, 0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0, 0], [0, 0, 0, 0, 0]])),
+            ("halite_map", json!([[0, 0, 0, 0, 0]])),
+            ("energy", json!([])),
+            ("history", json!([{ "turn_number": 1, "ships": [[0, 1, 0, 0, 0]], "dropoffs": [], "energy": [0],
+                "halite_changes": [] }])),
+        ];
+        for (key, value) in bad {
+            let mut v = good.clone();
+            v[key] = value.clone();
+            assert!(Game::from_json(&v).is_err(), "{} = {}", key, value);
+        }
+    }
+
+    fn lines(text: &[&str]) -> LineReader<::std::vec::IntoIter<String>> {
+        LineReader::new(text.iter().map(|l| l.to_string()).collect::<Vec<String>>().into_iter())
+    }
+
+    #[test]
+    fn skip_to_frame_passes_a_map_update_count_equal_to_the_turn() {
+        let mut game = blank_game(5, 4);
+        let mut input = lines(&[
+            "1", "0 1 0 0", "0 9 9 0", // a ship off the map
+            "2", "0 0 5", "1 0 5", // the rest of that frame: two map updates
+            "2", "0 1 0 1000", "7 3 2 0", "1", "4 1 30",
+        ]);
+        assert!(game.update_frame(&mut input).is_err());
+        game.skip_to_frame(&mut input, 2).unwrap();
+        game.update_frame(&mut input).unwrap();
+        assert_eq!(game.turn_number, 1);
+        assert_eq!(game.energy, vec![1000]);
+        assert_eq!(game.ships[&7].pos, Point { x: 3, y: 2 });
+        assert_eq!(game.halite_at(Point { x: 4, y: 1 }), 30);
+    }
+
+    #[test]
+    fn skip_to_frame_needs_every_player_header() {
+        let game = blank_game(5, 4);
+        let mut input = lines(&["2", "0 1 0 0", "5"]);
+        assert!(!game.frame_starts(&mut input, 2)); // the ship line is missing
+        let mut input = lines(&["2", "1 0 0 0"]);
+        assert!(!game.frame_starts(&mut input, 2));
+        let mut input = lines(&["2", "0 1 1 0", "3 0 0 0", "4 1 1"]);
+        assert!(game.frame_starts(&mut input, 2));
+        assert_eq!(input.next_line("").unwrap(), "2"); // peeking consumed nothing
+    }
+
+    // nearest source and its distance for every tile, ties going to the earlier source
+    fn brute_force_voronoi(game: &Game, sources: &[Point]) -> Vec<(usize, usize)> {
+        all_tiles(game).into_iter()
+            .map(|p| (0..sources.len()).map(|i| (game.dist(sources[i], p), i)).min().unwrap())
+            .map(|(d, i)| (i, d))
+            .collect()
+    }
+
+    fn voronoi(game: &Game, field: &DistanceField) -> Vec<(usize, usize)> {
+        all_tiles(game).into_iter().map(|p| (*field.nearest.get(p), field.dist(p))).collect()
+    }
+
+    #[test]
+    fn distance_field_matches_brute_force_voronoi() {
+        for game in every_size() {
+            for num_sources in 1..6 {
+                // spread out, with repeats on the smallest maps
+                let sources: Vec<Point> = (0..num_sources)
+                    .map(|i| Point { x: ((7*i + 3) % game.width) as isize, y: ((5*i + 1) % game.height) as isize })
+                    .collect();
+                let expected = brute_force_voronoi(&game, &sources);
+                let field = DistanceField::new(game.width, game.height, &sources, &unit_cost);
+                assert_eq!(voronoi(&game, &field), expected,
+                    "{}x{} with sources {:?}", game.width, game.height, sources);
+
+                let mut grown = DistanceField::new(game.width, game.height, &sources[..1], &unit_cost);
+                for &s in &sources[1..] {
+                    grown.add_source(s, &unit_cost);
+                }
+                assert_eq!(voronoi(&game, &grown), expected,
+                    "{}x{} adding sources {:?} one by one", game.width, game.height, sources);
+            }
+        }
+    }
 }
\ No newline at end of file
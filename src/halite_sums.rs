@@ -0,0 +1,246 @@
+use std::cmp::min;
+use game::GMap;
+use data::Point;
+
+// Prefix sums over the halite map for O(1) window sums on the torus.
+//
+// Square windows come from an ordinary summed-area table, split where the window wraps.
+// Diamonds |dx| + |dy| <= r are squares after turning the map 45 degrees (u = x + y,
+// v = x - y), so a second table holds the map padded by `pad` tiles on every side (a
+// copy of what is across the edge) and rotated. Diamonds wider than that pad would
+// overlap themselves, and are summed row by row instead.
+#[derive(Debug, Clone)]
+pub struct HaliteSums {
+    width: usize,
+    height: usize,
+    cells: Vec<usize>,
+    prefix: Vec<usize>, // prefix[y*(width+1) + x]: sum of the cells above y and left of x
+    pad: usize,
+    side: usize, // the rotated table is side x side
+    rotated: Vec<usize>,
+    rotated_prefix: Vec<usize>,
+    // first (row, column) of each prefix table that has to be recomputed
+    dirty: Option<(usize, usize)>,
+    rotated_dirty: Option<(usize, usize)>,
+}
+
+fn merge_dirty(dirty: &mut Option<(usize, usize)>, row: usize, col: usize) {
+    *dirty = Some(match *dirty {
+        Some((r, c)) => (min(r, row), min(c, col)),
+        None => (row, col),
+    });
+}
+
+// recomputes the prefix sums of rows row0.. and columns col0.. of a num_rows x num_cols table
+fn refresh_prefix(prefix: &mut [usize], cells: &[usize], num_rows: usize, num_cols: usize, (row0, col0): (usize, usize)) {
+    let stride = num_cols+1;
+    for i in (row0+1)..(num_rows+1) {
+        for j in (col0+1)..(num_cols+1) {
+            prefix[i*stride + j] = cells[(i-1)*num_cols + j-1] + prefix[(i-1)*stride + j] + prefix[i*stride + j-1]
+                - prefix[(i-1)*stride + j-1];
+        }
+    }
+}
+
+impl HaliteSums {
+    pub fn new(halite_map: &GMap<usize>) -> HaliteSums {
+        let (width, height) = (halite_map.width, halite_map.height);
+        let pad = min(width, height).saturating_sub(1)/2;
+        let side = (width + 2*pad) + (height + 2*pad) - 1;
+        let mut sums = HaliteSums {
+            width, height,
+            cells: vec![0; width*height],
+            prefix: vec![0; (width+1)*(height+1)],
+            pad, side,
+            rotated: vec![0; side*side],
+            rotated_prefix: vec![0; (side+1)*(side+1)],
+            dirty: None,
+            rotated_dirty: None,
+        };
+        for (pos, &halite) in halite_map.points() {
+            sums.set(pos, halite);
+        }
+        sums.refresh();
+        sums
+    }
+
+    // the position of a padded cell in the rotated table
+    fn rotate(&self, ex: usize, ey: usize) -> (usize, usize) {
+        let padded_height = self.height + 2*self.pad;
+        (ex + ey, ex + padded_height - 1 - ey)
+    }
+
+    // takes effect at the next refresh
+    pub fn set(&mut self, pos: Point, halite: usize) {
+        let x = pos.x.rem_euclid(self.width as isize) as usize;
+        let y = pos.y.rem_euclid(self.height as isize) as usize;
+        if self.cells[y*self.width + x] == halite {
+            return;
+        }
+        self.cells[y*self.width + x] = halite;
+        merge_dirty(&mut self.dirty, y, x);
+
+        // every copy of the cell in the padded map, the original plus up to three across edges
+        let (padded_width, padded_height) = (self.width + 2*self.pad, self.height + 2*self.pad);
+        for ex in [x + self.pad, x + self.pad + self.width, (x + self.pad).wrapping_sub(self.width)].iter()
+            .filter(|&&ex| ex < padded_width) {
+            for ey in [y + self.pad, y + self.pad + self.height, (y + self.pad).wrapping_sub(self.height)].iter()
+                .filter(|&&ey| ey < padded_height) {
+                let (u, v) = self.rotate(*ex, *ey);
+                self.rotated[u*self.side + v] = halite;
+                merge_dirty(&mut self.rotated_dirty, u, v);
+            }
+        }
+    }
+
+    // brings the prefix sums up to date after set; only rows and columns at or past the
+    // first changed cell are recomputed
+    pub fn refresh(&mut self) {
+        if let Some(from) = self.dirty.take() {
+            refresh_prefix(&mut self.prefix, &self.cells, self.height, self.width, from);
+        }
+        if let Some(from) = self.rotated_dirty.take() {
+            refresh_prefix(&mut self.rotated_prefix, &self.rotated, self.side, self.side, from);
+        }
+    }
+
+    // cells x0..x1, y0..y1 of the map, no wrapping
+    fn rect(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> usize {
+        let stride = self.width+1;
+        self.prefix[y1*stride + x1] + self.prefix[y0*stride + x0] - self.prefix[y0*stride + x1] - self.prefix[y1*stride + x0]
+    }
+
+    // len cells starting at start and wrapping around, as at most two plain ranges
+    fn wrapped_ranges(start: isize, len: usize, size: usize) -> Vec<(usize, usize)> {
+        if len >= size {
+            return vec![(0, size)];
+        }
+        let start = start.rem_euclid(size as isize) as usize;
+        if start + len <= size {
+            vec![(start, start + len)]
+        } else {
+            vec![(start, size), (0, start + len - size)]
+        }
+    }
+
+    // sum of a w x h window whose top left corner is at corner, wrapping around the map
+    pub fn window(&self, corner: Point, w: usize, h: usize) -> usize {
+        debug_assert!(self.dirty.is_none(), "HaliteSums used before refresh");
+        let mut sum = 0;
+        for &(x0, x1) in &HaliteSums::wrapped_ranges(corner.x, w, self.width) {
+            for &(y0, y1) in &HaliteSums::wrapped_ranges(corner.y, h, self.height) {
+                sum += self.rect(x0, y0, x1, y1);
+            }
+        }
+        sum
+    }
+
+    // the (2r+1) x (2r+1) square around center, each tile counted once on small maps
+    pub fn square(&self, center: Point, r: usize) -> usize {
+        let r_i = r as isize;
+        self.window(Point { x: center.x - r_i, y: center.y - r_i }, 2*r+1, 2*r+1)
+    }
+
+    // the tiles of Game::tiles_within_dist(center, r)
+    pub fn diamond(&self, center: Point, r: usize) -> usize {
+        debug_assert!(self.rotated_dirty.is_none(), "HaliteSums used before refresh");
+        let (w, h) = (self.width as isize, self.height as isize);
+        if r <= self.pad {
+            let ex = center.x.rem_euclid(w) as usize + self.pad;
+            let ey = center.y.rem_euclid(h) as usize + self.pad;
+            let (u, v) = self.rotate(ex, ey);
+            let stride = self.side+1;
+            let (u0, u1, v0, v1) = (u - r, u + r + 1, v - r, v + r + 1);
+            return self.rotated_prefix[u1*stride + v1] + self.rotated_prefix[u0*stride + v0]
+                - self.rotated_prefix[u0*stride + v1] - self.rotated_prefix[u1*stride + v0];
+        }
+
+        // one row at a time, over the offsets that are the shortest way to their tile
+        let r_i = r as isize;
+        ((-(h-1)/2)..(h/2+1))
+            .filter(|dy| dy.abs() <= r_i)
+            .map(|dy| {
+                let rem = r_i - dy.abs();
+                let (lo, hi) = (-min(rem, (w-1)/2), min(rem, w/2));
+                self.window(Point { x: center.x + lo, y: center.y + dy }, (hi - lo + 1) as usize, 1)
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::prng::XorShiftRng;
+
+    const MAX_SIZE: usize = 13;
+
+    fn all_tiles(width: usize, height: usize) -> Vec<Point> {
+        (0..height).flat_map(|y| (0..width).map(move |x| Point { x: x as isize, y: y as isize })).collect()
+    }
+
+    // how far apart two coordinates are around a loop of the given size
+    fn wrap_dist(a: isize, b: isize, size: usize) -> usize {
+        let d = (a - b).rem_euclid(size as isize) as usize;
+        min(d, size - d)
+    }
+
+    fn brute_force_diamond(map: &GMap<usize>, center: Point, r: usize) -> usize {
+        all_tiles(map.width, map.height).into_iter()
+            .filter(|p| wrap_dist(p.x, center.x, map.width) + wrap_dist(p.y, center.y, map.height) <= r)
+            .map(|p| *map.get(p))
+            .sum()
+    }
+
+    // the columns (or rows) center-r..=center+r, each once even if they wrap onto each other
+    fn in_span(a: isize, center: isize, r: usize, size: usize) -> bool {
+        2*r+1 >= size || (a - center + r as isize).rem_euclid(size as isize) as usize <= 2*r
+    }
+
+    fn brute_force_square(map: &GMap<usize>, center: Point, r: usize) -> usize {
+        all_tiles(map.width, map.height).into_iter()
+            .filter(|p| in_span(p.x, center.x, r, map.width) && in_span(p.y, center.y, r, map.height))
+            .map(|p| *map.get(p))
+            .sum()
+    }
+
+    fn assert_matches_brute_force(sums: &HaliteSums, map: &GMap<usize>, round: usize) {
+        let max_r = (map.width + map.height)/2 + 1;
+        for center in all_tiles(map.width, map.height) {
+            for r in 0..(max_r+1) {
+                assert_eq!(sums.diamond(center, r), brute_force_diamond(map, center, r),
+                    "{}x{} after {} rounds of updates: diamond at {:?}, r {}", map.width, map.height, round, center, r);
+                assert_eq!(sums.square(center, r), brute_force_square(map, center, r),
+                    "{}x{} after {} rounds of updates: square at {:?}, r {}", map.width, map.height, round, center, r);
+            }
+        }
+    }
+
+    #[test]
+    fn sums_match_brute_force_after_updates() {
+        let mut rng = XorShiftRng::seed_from_u64(20);
+        for width in 1..(MAX_SIZE+1) {
+            for height in 1..(MAX_SIZE+1) {
+                let mut map = GMap::new(width, height, 0);
+                for p in all_tiles(width, height) {
+                    *map.get_mut(p) = rng.gen_range(0, 1000);
+                }
+                let mut sums = HaliteSums::new(&map);
+                assert_matches_brute_force(&sums, &map, 0);
+
+                // a few tiles at a time, as map updates come in, sometimes to the value they had
+                for round in 1..3 {
+                    for _ in 0..rng.gen_range(1, 4) {
+                        let p = Point { x: rng.gen_range(0, width) as isize, y: rng.gen_range(0, height) as isize };
+                        let halite = if rng.gen_range(0, 4) == 0 { *map.get(p) } else { rng.gen_range(0, 1000) };
+                        *map.get_mut(p) = halite;
+                        sums.set(p, halite);
+                    }
+                    sums.refresh();
+                    assert_matches_brute_force(&sums, &map, round);
+                }
+            }
+        }
+    }
+}
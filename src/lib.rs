@@ -1,14 +1,36 @@
 #[macro_use] extern crate log;
 extern crate simplelog;
 extern crate rand;
-extern crate serde_json;
+#[macro_use] extern crate serde_json;
 extern crate pathfinding;
+extern crate zstd;
 
 mod game;
 mod data;
 mod bot_logic;
+mod phases;
 mod disjoint_set;
+mod assignment;
+mod cooperative;
+mod search;
+mod distance;
+mod halite_sums;
+pub mod enemy_model;
+mod frame_diff;
+mod opponents;
+mod engine;
+pub mod mapgen;
+mod replay;
+pub mod snapshot;
+pub mod strategy;
 
 pub use bot_logic::Logic;
-pub use game::{Game, parse_line_of_nums};
-pub use data::{Factory, Dropoff, Ship, Direction, Point};
\ No newline at end of file
+pub use strategy::Strategy;
+pub use game::{Game, Frame, Constants, GMap, LineReader, ParseError, parse_line_of_nums, HISTORY_LEN};
+pub use distance::{DistanceField, HomeField};
+pub use halite_sums::HaliteSums;
+pub use frame_diff::{FrameEvent, PrevFrame, diff_frames};
+pub use opponents::{OpponentTracker, OpponentProfile};
+pub use data::{Factory, Dropoff, Ship, Direction, Point, ShipCommand, TurnCommands};
+pub use engine::{Engine, Event, PlayerStats};
+pub use replay::{Replay, ReplayFrame, ReplayWriter};
\ No newline at end of file
@@ -8,42 +8,124 @@ use serde_json::Value;
 use simplelog::*;
 use std::io::{BufRead, BufReader, stdin};
 use std::fs::File;
+use std::env;
+use std::process;
+use std::collections::HashMap;
 
-use my_bot::Game;
+use my_bot::{Game, LineReader, ParseError, snapshot, strategy};
+use my_bot::enemy_model::EnemyMoveModel;
 
 
 fn main() {
+    // --snapshot-dir DIR saves every turn (or only --snapshot-turn N) for the rerun tool,
+    // --strategy NAME picks one of strategy::STRATEGY_NAMES,
+    // --enemy-model FILE loads weights written by the enemy_model tool
+    let args: Vec<String> = env::args().collect();
+    let arg_value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i+1));
+    let strategy_name = arg_value("--strategy").map_or(strategy::STRATEGY_NAMES[0], |s| s.as_str());
+    let mut strategy = match strategy::by_name(strategy_name) {
+        Some(s) => s,
+        None => {
+            eprintln!("unknown strategy {}, expected one of {:?}", strategy_name, strategy::STRATEGY_NAMES);
+            process::exit(1);
+        },
+    };
+    if let Some(path) = arg_value("--enemy-model") {
+        match EnemyMoveModel::load(path) {
+            Ok(model) => strategy.set_enemy_model(model),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            },
+        }
+    }
+    let snapshot_dir = arg_value("--snapshot-dir").cloned();
+    let snapshot_turn = arg_value("--snapshot-turn").and_then(|t| t.parse::<usize>().ok());
+
 	let stdin = stdin();
 	let reader = BufReader::new(stdin);
-	let mut lines_iter = reader.lines().map(|l| l.unwrap());
+	let mut lines_iter = LineReader::new(reader.lines().map_while(Result::ok));
+
+    let (constants, num_players, my_pid) = match read_handshake(&mut lines_iter) {
+        Ok(handshake) => handshake,
+        Err(e) => {
+            // without a player id the logs get a name no player can have
+            init_logs("unknown");
+            error!("could not read the pre-game: {}", e);
+            process::exit(1);
+        },
+    };
+
+    init_logs(&my_pid.to_string());
+    warn!("[");
 
-    let constants: Value = serde_json::from_str(&lines_iter.next().unwrap()).unwrap();
+    let mut game = match Game::init(&mut lines_iter, constants, num_players, my_pid) {
+        Ok(game) => game,
+        Err(e) => {
+            error!("could not read the pre-game: {}", e);
+            process::exit(1);
+        },
+    };
 
-    let player_info = my_bot::parse_line_of_nums(&mut lines_iter);
-    let num_players = player_info[0];
-    let my_pid = player_info[1];
+    strategy.on_init(&game);
+	game.ready("jank_bot_17");
+
+	let mut next_turn = 1; // the turn number the engine sends next
+	let mut out_of_step = false;
+	loop {
+		if out_of_step {
+			// the rest of the frame that could not be read comes before the next one
+			if let Err(e) = game.skip_to_frame(&mut lines_iter, next_turn) {
+				error!("could not find the next frame: {}", e);
+				break;
+			}
+		}
+		if let Err(e) = game.update_frame(&mut lines_iter) {
+			error!("could not read a frame, skipping the turn: {}", e);
+			if e.raw.is_none() {
+				break; // the engine has closed our input
+			}
+			next_turn += 1;
+			out_of_step = true;
+			Game::end_turn((false, HashMap::new()));
+			continue;
+		}
+		next_turn = game.turn_number + 2;
+		out_of_step = false;
+		if let Some(ref dir) = snapshot_dir {
+			if snapshot_turn.is_none_or(|t| t == game.turn_number) {
+				let path = format!("{}/turn-{}-p{}.json", dir, game.turn_number, my_pid);
+				if let Err(e) = snapshot::save(&path, &game, strategy_name, &*strategy) {
+					error!("could not save snapshot {}: {}", path, e);
+				}
+			}
+		}
+		Game::end_turn(strategy.make_moves(&game));
+	}
+}
 
-    let mut game = Game::init(&mut lines_iter, constants, num_players, my_pid);
-    let _ = CombinedLogger::init(
+// Jank-log-NAME.log gets everything, f-NAME.log only warnings and errors
+fn init_logs(name: &str) {
+    CombinedLogger::init(
     	vec![
 		    WriteLogger::new(
 		    	LevelFilter::Info,
 		    	Config {time: None, level: None, target: None, location: None, time_format: None},
-		    	File::create(format!("Jank-log-{}.log", my_pid)).unwrap()),
+		    	File::create(format!("Jank-log-{}.log", name)).unwrap()),
 		    WriteLogger::new(
 		    	LevelFilter::Warn,
 		    	Config {time: None, level: None, target: None, location: None, time_format: None},
-		    	File::create(format!("f-{}.log", my_pid)).unwrap())
+		    	File::create(format!("f-{}.log", name)).unwrap())
     	]
     ).unwrap();
-    warn!("[");
+}
 
-    let mut logic = my_bot::Logic::new();
-
-	game.ready("jank_bot_17");
-
-	loop {
-		game.update_frame(&mut lines_iter);
-		Game::end_turn(logic.make_moves(&game));
-	}
+// the constants and the "num_players my_pid" line that come before Game::init's part
+fn read_handshake<I: Iterator<Item = String>>(lines_iter: &mut LineReader<I>)
+    -> Result<(Value, usize, usize), ParseError> {
+    let line = lines_iter.next_line("constants JSON")?;
+    let constants: Value = serde_json::from_str(&line)
+        .map_err(|_| lines_iter.error("constants JSON", &line))?;
+    let player_info = my_bot::parse_line_of_nums(lines_iter, "\"num_players my_pid\"", 2)?;
+    Ok((constants, player_info[0], player_info[1]))
 }
\ No newline at end of file
@@ -0,0 +1,235 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::io;
+use rand::{Rng, SeedableRng};
+use rand::prng::XorShiftRng;
+use data::*;
+use game::GMap;
+
+// fractal value noise, after the official generator
+const OCTAVES: usize = 4;
+const PERSISTENCE: f64 = 0.6;
+const FACTOR_EXP: f64 = 2.5;
+const MIN_CELL_HALITE: f64 = 600.0;
+const MAX_CELL_HALITE: f64 = 1000.0;
+
+// Symmetric toroidal map for local games: one tile is filled in and then mirrored,
+// left/right for 2 players and into all four quadrants for 4 players. On odd sizes
+// the middle row or column is its own mirror image.
+// Official sizes are 32, 40, 48, 56 and 64.
+pub fn generate(num_players: usize, width: usize, height: usize, seed: u64) -> (GMap<usize>, Vec<Factory>) {
+    assert!(num_players == 2 || num_players == 4);
+    let mut rng = XorShiftRng::seed_from_u64(seed);
+    let tile_width = width.div_ceil(2);
+    let tile_height = if num_players == 4 { height.div_ceil(2) } else { height };
+
+    let noise = fractal_noise(&mut rng, tile_width, tile_height);
+    let peak = MIN_CELL_HALITE + (MAX_CELL_HALITE - MIN_CELL_HALITE)*rng.gen::<f64>();
+    let mut tile: Vec<Vec<usize>> = noise.iter()
+        .map(|row| row.iter().map(|v| (peak*v.powf(FACTOR_EXP)) as usize).collect())
+        .collect();
+
+    let factory = Point { x: (tile_width/2) as isize, y: (tile_height/2) as isize };
+    tile[factory.y as usize][factory.x as usize] = 0;
+    mirror_tile(&tile, num_players, width, height, factory)
+}
+
+// sum of bilinearly interpolated random lattices with doubling frequency, scaled to [0, 1]
+fn fractal_noise<R: Rng>(rng: &mut R, width: usize, height: usize) -> Vec<Vec<f64>> {
+    let mut noise = vec![vec![0.0; width]; height];
+    let mut amplitude = 1.0;
+    for octave in 0..OCTAVES {
+        let cells = 2 << octave;
+        let lattice: Vec<Vec<f64>> = (0..(cells+1))
+            .map(|_| (0..(cells+1)).map(|_| rng.gen::<f64>()).collect())
+            .collect();
+        for (y, row) in noise.iter_mut().enumerate() {
+            let fy = y as f64*cells as f64/height as f64;
+            let (ly, ty) = (fy as usize, smoothstep(fy.fract()));
+            for (x, v) in row.iter_mut().enumerate() {
+                let fx = x as f64*cells as f64/width as f64;
+                let (lx, tx) = (fx as usize, smoothstep(fx.fract()));
+                let top = lattice[ly][lx]*(1.0-tx) + lattice[ly][lx+1]*tx;
+                let bottom = lattice[ly+1][lx]*(1.0-tx) + lattice[ly+1][lx+1]*tx;
+                *v += amplitude*(top*(1.0-ty) + bottom*ty);
+            }
+        }
+        amplitude *= PERSISTENCE;
+    }
+
+    let lo = noise.iter().flat_map(|row| row.iter()).cloned().fold(f64::INFINITY, f64::min);
+    let hi = noise.iter().flat_map(|row| row.iter()).cloned().fold(f64::NEG_INFINITY, f64::max);
+    for v in noise.iter_mut().flat_map(|row| row.iter_mut()) {
+        *v = if hi > lo { (*v - lo)/(hi - lo) } else { 0.0 };
+    }
+    noise
+}
+
+fn smoothstep(t: f64) -> f64 {
+    t*t*(3.0 - 2.0*t)
+}
+
+fn mirror_tile(tile: &[Vec<usize>], num_players: usize, width: usize, height: usize,
+    factory: Point) -> (GMap<usize>, Vec<Factory>) {
+    let tile_width = tile[0].len();
+    let tile_height = tile.len();
+    let gmap: Vec<Vec<usize>> = (0..height).map(|y| {
+            let ty = if y < tile_height { y } else { height - 1 - y };
+            (0..width).map(|x| {
+                    let tx = if x < tile_width { x } else { width - 1 - x };
+                    tile[ty][tx]
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut factories = Vec::new();
+    for player in 0..num_players {
+        let x = if player % 2 == 0 { factory.x } else { width as isize - 1 - factory.x };
+        let y = if player < 2 { factory.y } else { height as isize - 1 - factory.y };
+        factories.push(Factory { player, pos: Point { x, y } });
+    }
+    (GMap::from_rows(gmap), factories)
+}
+
+// Text form of a map: the number of players, one "player x y" line per factory,
+// "width height", then one line of halite per row (the same layout the engine
+// sends during the pre-game).
+pub fn export(halite_map: &GMap<usize>, factories: &[Factory]) -> String {
+    let mut lines = vec![factories.len().to_string()];
+    for f in factories {
+        lines.push(format!("{} {} {}", f.player, f.pos.x, f.pos.y));
+    }
+    lines.push(format!("{} {}", halite_map.width, halite_map.height));
+    for row in halite_map.rows() {
+        lines.push(row.iter().map(|h| h.to_string()).collect::<Vec<String>>().join(" "));
+    }
+    lines.join("\n") + "\n"
+}
+
+pub fn import(text: &str) -> Result<(GMap<usize>, Vec<Factory>), String> {
+    let mut lines = text.lines().enumerate();
+    let mut next_nums = |expected: &str| -> Result<Vec<usize>, String> {
+        let (i, line) = lines.next().ok_or_else(|| format!("map ends before {}", expected))?;
+        line.split_whitespace()
+            .map(|n| n.parse::<usize>().map_err(|_| format!("line {}: bad {} {:?}", i+1, expected, line)))
+            .collect()
+    };
+
+    let num_players = *next_nums("player count")?.first().ok_or("missing player count")?;
+    if num_players != 2 && num_players != 4 {
+        return Err(format!("player count should be 2 or 4, got {}", num_players));
+    }
+    let mut factories = Vec::new();
+    for _ in 0..num_players {
+        let f = next_nums("factory")?;
+        if f.len() != 3 {
+            return Err(format!("factory line should be \"player x y\", got {:?}", f));
+        }
+        factories.push(Factory { player: f[0], pos: Point { x: f[1] as isize, y: f[2] as isize } });
+    }
+    let dims = next_nums("dimensions")?;
+    if dims.len() != 2 {
+        return Err(format!("dimensions line should be \"width height\", got {:?}", dims));
+    }
+    let mut gmap = Vec::new();
+    for _ in 0..dims[1] {
+        let row = next_nums("halite row")?;
+        if row.len() != dims[0] {
+            return Err(format!("halite row has {} cells, expected {}", row.len(), dims[0]));
+        }
+        gmap.push(row);
+    }
+    if dims[0] == 0 || dims[1] == 0 {
+        return Err(format!("empty {}x{} map", dims[0], dims[1]));
+    }
+    check_factories(&mut factories, num_players, dims[0], dims[1])?;
+    Ok((GMap::from_rows(gmap), factories))
+}
+
+// one factory per player, on the map, sorted by player so factories[pid] is pid's
+fn check_factories(factories: &mut [Factory], num_players: usize, width: usize, height: usize)
+    -> Result<(), String> {
+    for f in factories.iter() {
+        if f.player >= num_players {
+            return Err(format!("factory for player {} in a {} player map", f.player, num_players));
+        }
+        if f.pos.x as usize >= width || f.pos.y as usize >= height {
+            return Err(format!("factory of player {} at {} {} is off the {}x{} map",
+                f.player, f.pos.x, f.pos.y, width, height));
+        }
+    }
+    factories.sort_by_key(|f| f.player);
+    for (i, f) in factories.iter().enumerate() {
+        if f.player != i {
+            // sorted and in range, so the first gap is either a repeat or a missing player
+            return Err(if i > 0 && factories[i-1].player == f.player {
+                format!("player {} has two factories", f.player)
+            } else {
+                format!("player {} has no factory", i)
+            });
+        }
+        if let Some(other) = factories[..i].iter().find(|o| o.pos == f.pos) {
+            return Err(format!("players {} and {} share a factory at {} {}", other.player, f.player, f.pos.x, f.pos.y));
+        }
+    }
+    Ok(())
+}
+
+pub fn save(path: &str, halite_map: &GMap<usize>, factories: &[Factory]) -> io::Result<()> {
+    File::create(path)?.write_all(export(halite_map, factories).as_bytes())
+}
+
+pub fn load(path: &str) -> Result<(GMap<usize>, Vec<Factory>), String> {
+    let mut text = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut text))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    import(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 3x2 map with the given factory lines
+    fn map_text(factories: &[&str]) -> String {
+        let mut lines = vec![factories.len().to_string()];
+        lines.extend(factories.iter().map(|f| f.to_string()));
+        lines.extend(vec!["3 2".to_owned(), "1 2 3".to_owned(), "4 5 6".to_owned()]);
+        lines.join("\n")
+    }
+
+    #[test]
+    fn import_reads_back_export() {
+        for &num_players in &[2, 4] {
+            let (halite_map, factories) = generate(num_players, 9, 7, 1);
+            let (read_map, read_factories) = import(&export(&halite_map, &factories)).unwrap();
+            assert!(read_map.rows().eq(halite_map.rows()));
+            let places = |fs: &[Factory]| fs.iter().map(|f| (f.player, f.pos)).collect::<Vec<(usize, Point)>>();
+            assert_eq!(places(&read_factories), places(&factories));
+        }
+    }
+
+    #[test]
+    fn import_sorts_factories_by_player() {
+        let (_, factories) = import(&map_text(&["1 2 1", "0 0 0"])).unwrap();
+        assert_eq!(factories.iter().map(|f| f.player).collect::<Vec<usize>>(), vec![0, 1]);
+    }
+
+    #[test]
+    fn import_rejects_bad_factories() {
+        let bad: &[&[&str]] = &[
+            &["0 0 0"],                           // one player
+            &["0 0 0", "2 1 1"],                  // player out of range
+            &["0 0 0", "0 1 1"],                  // player 0 twice, player 1 missing
+            &["1 0 0", "1 1 1"],                  // player 0 missing
+            &["0 0 0", "1 3 0"],                  // off the map to the right
+            &["0 0 0", "1 0 2"],                  // off the map below
+            &["0 1 1", "1 1 1"],                  // same tile
+            &["0 0 0", "1 1 1", "2 2 0"],         // three players
+        ];
+        for factories in bad {
+            assert!(import(&map_text(factories)).is_err(), "{:?}", factories);
+        }
+    }
+}
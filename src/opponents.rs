@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use serde_json::Value;
+use game::{Game, HISTORY_LEN};
+use data::Point;
+use frame_diff::{FrameEvent, PrevFrame, diff_frames};
+
+// What we assume of a player before seeing any of its ships: rams like the hand-tuned
+// enemy forecast expects, steps away from half its contacts and heads home nearly full.
+// The weights are how many observations count as much as the prior.
+const RAM_PRIOR: f64 = 0.03;
+const RAM_PRIOR_WEIGHT: f64 = 20.0;
+const YIELD_PRIOR: f64 = 0.5;
+const YIELD_PRIOR_WEIGHT: f64 = 20.0;
+const RETURN_PRIOR: f64 = 950.0;
+const RETURN_PRIOR_WEIGHT: f64 = 5.0;
+// contact_risk scales how hard my ships avoid and attack a player, so it takes more
+// contacts to move it off 1, and it never drops below the floor
+const CONTACT_RISK_WEIGHT: f64 = 40.0;
+const MIN_CONTACT_RISK: f64 = 0.25;
+
+// the mean of count observations adding up to total, pulled toward prior
+fn smoothed(prior: f64, weight: f64, total: f64, count: usize) -> f64 {
+    prior + (total - prior*count as f64)/(weight + count as f64)
+}
+
+// How one player has behaved so far this game, from the moves of its ships.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpponentProfile {
+    pub contacts: usize, // turns one of its ships could move and started next to another player's ship
+    pub rams: usize, // contacts where the ship moved onto the tile of one of those ships
+    pub yields: usize, // contacts where the ship moved out of reach of every ship it was next to
+    pub deposits: usize,
+    pub deposited: usize, // cargo of those deposits
+    pub returns: usize, // deposits that ended a trip home, see OpponentTracker::departure_cargo
+    pub return_cargo: usize, // what those ships carried when they set off home
+    pub ships_lost: usize, // ships that disappeared without becoming a dropoff
+    pub dropoff_turns: Vec<usize>,
+}
+
+impl OpponentProfile {
+    pub fn ram_rate(&self) -> f64 {
+        smoothed(RAM_PRIOR, RAM_PRIOR_WEIGHT, self.rams as f64, self.contacts)
+    }
+
+    pub fn yield_rate(&self) -> f64 {
+        smoothed(YIELD_PRIOR, YIELD_PRIOR_WEIGHT, self.yields as f64, self.contacts)
+    }
+
+    // the cargo its ships head home with
+    pub fn return_threshold(&self) -> f64 {
+        smoothed(RETURN_PRIOR, RETURN_PRIOR_WEIGHT, self.return_cargo as f64, self.returns)
+    }
+
+    // How much more dangerous than assumed it is to stand next to one of its ships: what
+    // the contacts so far show, (1 - yields/contacts)/(1 - YIELD_PRIOR), trusted in
+    // proportion to how many there were. 1 before anything is known, up to 2 for a player
+    // that never backs off and down to MIN_CONTACT_RISK for one that always does.
+    pub fn contact_risk(&self) -> f64 {
+        if self.contacts == 0 {
+            return 1.0;
+        }
+        let n = self.contacts as f64;
+        let observed = (1.0 - self.yields as f64/n)/(1.0 - YIELD_PRIOR);
+        let trust = n/(n + CONTACT_RISK_WEIGHT);
+        (1.0 + trust*(observed - 1.0)).max(MIN_CONTACT_RISK)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "contacts": self.contacts,
+            "rams": self.rams,
+            "yields": self.yields,
+            "deposits": self.deposits,
+            "deposited": self.deposited,
+            "returns": self.returns,
+            "return_cargo": self.return_cargo,
+            "ships_lost": self.ships_lost,
+            "dropoff_turns": self.dropoff_turns,
+        })
+    }
+
+    fn from_json(v: &Value) -> Result<OpponentProfile, String> {
+        let count = |key: &str| v[key].as_u64().map(|n| n as usize)
+            .ok_or_else(|| format!("opponent profile has no {}", key));
+        let dropoff_turns = v["dropoff_turns"].as_array().ok_or("opponent profile has no dropoff_turns")?
+            .iter()
+            .map(|t| t.as_u64().map(|t| t as usize).ok_or_else(|| format!("bad dropoff turn {}", t)))
+            .collect::<Result<Vec<usize>, String>>()?;
+        Ok(OpponentProfile {
+            contacts: count("contacts")?,
+            rams: count("rams")?,
+            yields: count("yields")?,
+            deposits: count("deposits")?,
+            deposited: count("deposited")?,
+            // profiles saved before trips home were told apart from other deposits have none
+            returns: count("returns").unwrap_or(0),
+            return_cargo: count("return_cargo").unwrap_or(0),
+            ships_lost: count("ships_lost")?,
+            dropoff_turns,
+        })
+    }
+}
+
+// Builds an OpponentProfile for every player, me included, from the events between each
+// frame and the one before it in game.history. Call update once at the start of every turn.
+#[derive(Default)]
+pub struct OpponentTracker {
+    pub profiles: Vec<OpponentProfile>,
+    events: Vec<FrameEvent>,
+}
+
+impl OpponentTracker {
+    pub fn new() -> OpponentTracker {
+        OpponentTracker {
+            profiles: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn profile(&self, player: usize) -> OpponentProfile {
+        self.profiles.get(player).cloned().unwrap_or_default()
+    }
+
+    // what happened between the frame passed to update and the one before it
+    pub fn events(&self) -> &[FrameEvent] {
+        &self.events
+    }
+
+    pub fn update(&mut self, game: &Game) {
+        if self.profiles.len() < game.num_players {
+            self.profiles.resize(game.num_players, OpponentProfile::default());
+        }
+
+        self.events = diff_frames(game);
+        if let Some(prev) = PrevFrame::of(game) {
+            // where each ship ended up, if it survived or it is known where it was destroyed
+            let mut end_pos: HashMap<usize, Point> = HashMap::new();
+            for e in &self.events {
+                match *e {
+                    FrameEvent::Moved { ship_id, from, dir, .. } => {
+                        end_pos.insert(ship_id, game.step_toward(from, dir));
+                    },
+                    FrameEvent::Constructed { player, pos, .. } => {
+                        info!("player {} built a dropoff at ({}, {}) on turn {}", player, pos.x, pos.y, game.turn_number);
+                        self.profiles[player].dropoff_turns.push(game.turn_number);
+                    },
+                    FrameEvent::Deposited { player, ship_id, pos, halite } => {
+                        let profile = &mut self.profiles[player];
+                        profile.deposits += 1;
+                        profile.deposited += halite;
+                        if let Some(cargo) = OpponentTracker::departure_cargo(game, ship_id, pos) {
+                            profile.returns += 1;
+                            profile.return_cargo += cargo;
+                        }
+                    },
+                    FrameEvent::Collision { pos, ref ships, .. } => {
+                        for s in ships {
+                            self.profiles[s.player].ships_lost += 1;
+                            if let Some(p) = pos {
+                                end_pos.insert(s.ship_id, p);
+                            }
+                        }
+                    },
+                    FrameEvent::Spawned { .. } => (),
+                }
+            }
+
+            // ships that could move and started next to another player's ship
+            for ship in prev.frame.ships.values().filter(|s| !prev.must_stay(s)) {
+                let others: Vec<Point> = game.neighbors(ship.pos).into_iter()
+                    .filter(|&p| prev.ship_at(p).is_some_and(|o| o.player != ship.player))
+                    .collect();
+                let end = match end_pos.get(&ship.ship_id) {
+                    Some(&end) if !others.is_empty() => end,
+                    _ => continue,
+                };
+                let profile = &mut self.profiles[ship.player];
+                profile.contacts += 1;
+                if others.contains(&end) {
+                    profile.rams += 1;
+                }
+                if game.ships.contains_key(&ship.ship_id) && others.iter().all(|&o| game.dist(end, o) > 1) {
+                    profile.yields += 1;
+                }
+            }
+        }
+    }
+
+    // What a ship that just deposited at pos carried on the last turn it was not yet
+    // heading there, i.e. when it decided to go home: before the trip's move costs, and
+    // with no cargo from tiles mined on the way. None for deposits that tell nothing about
+    // the return threshold: those in the last turns, when every ship goes home whatever it
+    // carries, and those of less than half a ship, dropped off by ships passing through.
+    fn departure_cargo(game: &Game, ship_id: usize, pos: Point) -> Option<usize> {
+        let turns_left = game.constants.max_turns.saturating_sub(game.turn_number);
+        if turns_left <= 2*game.max_dist() {
+            return None;
+        }
+        let trail = game.ship_history(ship_id, HISTORY_LEN);
+        let start = game.dist(trail.first()?.pos, pos);
+        // going back from the deposit, the turns where each step had brought it one closer
+        let (departed, _) = trail.iter().zip(start..)
+            .take_while(|&(ship, dist)| game.dist(ship.pos, pos) == dist)
+            .last()?;
+        let cargo = departed.halite;
+        if (cargo as usize) < game.constants.max_halite/2 {
+            return None;
+        }
+        Some(cargo as usize)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "profiles": self.profiles.iter().map(OpponentProfile::to_json).collect::<Vec<Value>>(),
+        })
+    }
+
+    pub fn from_json(v: &Value) -> Result<OpponentTracker, String> {
+        let mut tracker = OpponentTracker::new();
+        for p in v["profiles"].as_array().ok_or("opponent tracker has no profiles")? {
+            tracker.profiles.push(OpponentProfile::from_json(p)?);
+        }
+        Ok(tracker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::{Direction, ShipCommand};
+    use engine::Engine;
+
+    // Player 1 (factory at (12, 8)) mines a turn six tiles north of its factory on a map
+    // of 100 halite tiles, then heads straight home, while a second ship
+    // drops off a partial load on its way past. Moves pay 10% of the tile left,
+    // staying mines 25%. Returns the tracker once both deposited.
+    fn watch_trip_home(max_turns: usize) -> OpponentTracker {
+        let mut engine = Engine::flat(2, 16, 16, 100);
+        engine.constants.max_turns = max_turns;
+        *engine.halite_map.get_mut(Point { x: 12, y: 8 }) = 0; // so an empty ship can leave the factory
+        let full = engine.place_ship(1, 12, 2, 775);
+        let partial = engine.place_ship(1, 12, 9, 300);
+        let mut game = engine.game_view(0);
+        let mut tracker = OpponentTracker::new();
+        tracker.update(&game);
+
+        let mut turn = |engine: &mut Engine, full_dir, partial_dir| {
+            let mut commands = vec![(false, HashMap::new()); 2];
+            commands[1].1.insert(full, ShipCommand::MoveShip(full_dir));
+            commands[1].1.insert(partial, ShipCommand::MoveShip(partial_dir));
+            engine.step(&commands);
+            engine.update_view(&mut game);
+            tracker.update(&game);
+        };
+        turn(&mut engine, Direction::Still, Direction::North); // mines 25 to 800, partial drops off
+        turn(&mut engine, Direction::South, Direction::East); // partial clears the factory
+        for _ in 0..5 {
+            turn(&mut engine, Direction::South, Direction::Still);
+        }
+        tracker
+    }
+
+    #[test]
+    fn return_threshold_counts_cargo_when_ships_turn_home() {
+        let profile = watch_trip_home(1000).profile(1);
+        assert_eq!((profile.deposits, profile.deposited), (2, 300 - 10 + 800 - 7 - 5*10));
+        assert_eq!((profile.returns, profile.return_cargo), (1, 800), "only the full ship's trip counts, at its cargo before moving");
+        assert_eq!(profile.return_threshold(), (RETURN_PRIOR*RETURN_PRIOR_WEIGHT + 800.0)/(RETURN_PRIOR_WEIGHT + 1.0));
+
+        let endgame = watch_trip_home(20).profile(1);
+        assert_eq!((endgame.deposits, endgame.returns), (2, 0), "deposits in the last turns tell nothing of the threshold");
+        assert_eq!(endgame.return_threshold(), RETURN_PRIOR);
+    }
+
+    #[test]
+    fn contact_risk_stays_near_the_prior_until_there_are_contacts() {
+        let risk = |contacts, yields| OpponentProfile { contacts, yields, ..OpponentProfile::default() }.contact_risk();
+        assert_eq!(risk(0, 0), 1.0);
+        assert!((risk(4, 4) - 1.0).abs() < 0.1, "a few yields barely move it");
+        assert!((risk(4, 0) - 1.0).abs() < 0.1, "a few rams barely move it");
+        assert!(risk(400, 0) > 1.8 && risk(400, 0) <= 2.0);
+        assert_eq!(risk(400, 200), 1.0);
+        assert_eq!(risk(10_000, 10_000), MIN_CONTACT_RISK, "a player that always yields still counts for something");
+        for contacts in 0..200 {
+            for yields in 0..contacts + 1 {
+                let r = risk(contacts, yields);
+                assert!((MIN_CONTACT_RISK..=2.0).contains(&r), "{} yields of {} contacts gave {}", yields, contacts, r);
+            }
+        }
+    }
+}
@@ -0,0 +1,1216 @@
+// The steps Logic::make_moves goes through each turn, in order:
+// StuckTracker, DropoffPlanner, GoalAssigner, CombatPlanner, MovePlanner, CollisionResolver.
+// Each one takes the Game plus the output of the earlier steps.
+
+use game::{Game, CellPriorityMax, GMap};
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::BinaryHeap;
+use std::iter::FromIterator;
+use data::{ShipCommand, Direction, Point, Ship};
+use std::cmp::min;
+use std::cmp::Ordering;
+use std::f32;
+use disjoint_set::DisjointSet;
+use assignment::{self, AssignMethod};
+use cooperative::{ReservationTable, TURN_COST, plan_path};
+use search::GridSearch;
+use bot_logic::{GoalType, ship_val, dropoff_proximity};
+use opponents::OpponentTracker;
+
+// stuck tracking
+// ==============
+
+// counts the turns each of my ships has stayed put with an enemy ship close by
+pub struct StuckTracker {
+	pub turns_stuck: HashMap<usize, usize>,
+}
+
+impl StuckTracker {
+	pub fn new() -> StuckTracker {
+		StuckTracker {
+			turns_stuck: HashMap::new(),
+		}
+	}
+
+	// call once at the start of every turn
+	pub fn update(&mut self, game: &Game) {
+		let me = game.my_pid;
+		let my_ships_ids = &game.ship_id_by_player[me];
+		self.turns_stuck.retain(|&id, _| my_ships_ids.contains(&id));
+		for &id in my_ships_ids {
+			let prev = game.ship_history(id, 1);
+			if prev.is_empty() {
+				self.turns_stuck.insert(id, 0);
+			} else {
+				let ship = game.ships.get(&id).unwrap();
+				let mut e_ship_near = false;
+				for p in game.tiles_between_dist(ship.pos, 1, 2) {
+					if let Some(&o_id) = game.ship_map.get(&p) {
+						if game.ships.get(&o_id).unwrap().player != me {
+							e_ship_near = true;
+							break;
+						}
+					}
+				}
+				if ship.pos == prev[0].pos && e_ship_near {
+					*self.turns_stuck.entry(id).or_insert(0) += 1;
+				} else {
+					self.turns_stuck.insert(id, 0);
+				}
+			}
+		}
+
+		for (&id, &turns) in &self.turns_stuck {
+			if turns >= 1 {
+				info!("ship {} stuck for {} turns", id, turns);
+			}
+		}
+	}
+}
+
+// dropoffs
+// ========
+
+pub struct DropoffCandidate {
+	pub center: Point,
+	pub dist: usize,
+}
+
+// minimum distance between a new dropoff and my existing drop points
+pub fn dropoff_spacing(game: &Game) -> usize {
+	if game.num_players == 4 && game.width <= 32 {11} else {15}
+}
+
+// keeps the list of places worth building a dropoff on, and picks the ship that builds one
+pub struct DropoffPlanner {
+	pub candidates: Vec<DropoffCandidate>,
+	pub saving_for_dropoff: bool,
+}
+
+pub struct DropoffPlan {
+	pub builder: Option<usize>, // the ship that turns into a dropoff this turn
+	pub halite_left: usize, // my halite after paying for it
+}
+
+impl DropoffPlanner {
+	pub fn new() -> DropoffPlanner {
+		DropoffPlanner {
+			candidates: Vec::new(),
+			saving_for_dropoff: false,
+		}
+	}
+
+	pub fn plan(&mut self, game: &Game) -> DropoffPlan {
+		let my_ships_ids = &game.ship_id_by_player[game.my_pid];
+		let dropoff_spacing = dropoff_spacing(game);
+		let mut halite_left = game.energy[game.my_pid];
+
+		let mut halite_densities: HashMap<Point, f32> = HashMap::new();
+		self.candidates.retain(|dc| {
+			let nearby_halite = game.halite_sums.diamond(dc.center, 5);
+			let nearby_halite_density = nearby_halite as f32/game.num_tiles_within_dist(5) as f32;
+			halite_densities.insert(dc.center, nearby_halite_density);
+			nearby_halite_density > 150.0 && game.my_drop_field.dist(dc.center) >= dropoff_spacing
+		}); // also that enemy is not outcrowding the dropoff site?
+
+		let mut dropoff_str = String::new();
+		for dc in &self.candidates {
+			dropoff_str.push_str(&format!("{{\"t\": {}, \"x\": {}, \"y\": {}, \"msg\": \"dc[r {}, dist {}, n_h_density {:.1}]\", \"color\": \"{}\"}},",
+						game.turn_number, dc.center.x, dc.center.y, dc.dist, game.my_drop_field.dist(dc.center),
+						halite_densities.get(&dc.center).unwrap(),
+						"#D000D0"));
+		}
+		warn!("{}", dropoff_str);
+
+		self.saving_for_dropoff = !self.candidates.is_empty() && my_ships_ids.len() > 15
+			&& game.turn_number < game.constants.max_turns - (game.width*3/2);
+
+		let mut builder = None;
+		if halite_left >= game.constants.dropoff_cost && self.saving_for_dropoff {
+			let mut best_score = -1.0;
+
+			for &id in my_ships_ids {
+				let ship = game.ships.get(&id).unwrap();
+				if game.my_drop_field.dist(ship.pos) >= dropoff_spacing // ship has to be far enough
+					&& !game.enemy_drop_pts.contains_key(&ship.pos) {
+					for dc in &self.candidates {
+						if game.dist(ship.pos, dc.center) <= dc.dist { // ship has to be close to the dropoff candidate
+							let score = halite_densities.get(&dc.center).unwrap()
+								+ 3.0 * game.my_drop_field.dist(ship.pos) as f32;
+							if score > best_score {
+								best_score = score;
+								builder = Some(id);
+							}
+						}
+					}
+				}
+			}
+
+			// actually assign the ship to build the dropoff
+			if builder.is_some() {
+				halite_left -= game.constants.dropoff_cost;
+				self.saving_for_dropoff = false;
+			}
+		}
+
+		if self.saving_for_dropoff {
+			info!("saving for dropoff on t {}", game.turn_number);
+		}
+		DropoffPlan { builder, halite_left }
+	}
+
+	// looks for clusters among the goals miners were sent to this turn
+	pub fn add_candidates(&mut self, game: &Game, v_goals: Vec<Point>) {
+		let dropoff_spacing = dropoff_spacing(game);
+
+		// get unique list of goal points, in the order they were picked
+		let mut goal_pos: Vec<Point> = Vec::new();
+		for p in v_goals {
+			if !goal_pos.contains(&p) {
+				goal_pos.push(p);
+			}
+		}
+
+		let dc_pos: Vec<Point> = self.candidates.iter().map(|dc| dc.center).collect();
+
+		let num_pos = goal_pos.len();
+		if num_pos >= 3 {
+			// find clusters
+			let mut ds = DisjointSet::make_singletons(num_pos);
+			for i in 0..(num_pos-1) {
+				for j in (i+1)..num_pos {
+					if game.dist(goal_pos[i], goal_pos[j]) <= 2 {
+						ds.unite(i, j);
+					}
+				}
+			}
+
+			let mut groups: BTreeMap<usize, Vec<Point>> = BTreeMap::new();
+			for (i, &p) in (0..ds.size).zip(&goal_pos) {
+				groups.entry(ds.parent[i]).or_default().push(p);
+			}
+
+			// find center/extent of group
+			for group in groups.values() {
+				if group.len() >= 3 {
+					let mut center = group[0];
+					let mut min_dist_total: usize = 1000;
+
+					for &p in group {
+						let dist_total: usize = group.iter().map(|&q| game.dist(p, q)).sum();
+						if dist_total < min_dist_total {
+							center = p;
+							min_dist_total = dist_total;
+						}
+					}
+
+					if !dc_pos.contains(&center) {
+						let dist = min(min_dist_total/group.len() + 2, 5);
+						let nearby_halite = game.halite_sums.diamond(center, 5);
+						let nearby_halite_density = nearby_halite as f32/game.num_tiles_within_dist(5) as f32;
+						let drop_dist = game.my_drop_field.dist(center);
+
+						warn!("{{\"t\": {}, \"x\": {}, \"y\": {}, \"msg\": \"dc[r {}, dist {}, n_h_density {:.1}]\", \"color\": \"{}\"}},",
+							game.turn_number, center.x, center.y, dist, drop_dist,
+							nearby_halite_density,
+							"#D0D000");
+
+						if nearby_halite_density + 3.0*drop_dist as f32 > 310.0 && drop_dist >= dropoff_spacing {
+							self.candidates.push(DropoffCandidate {
+								center, dist,
+							});
+						}
+					}
+				}
+			}
+		}
+	}
+}
+
+// which of my ships can pay to move this turn
+pub struct ShipLists {
+	pub movable: Vec<usize>,
+	pub immovable: Vec<usize>,
+}
+
+pub fn split_ships(game: &Game, builder: Option<usize>) -> ShipLists {
+	let mut lists = ShipLists { movable: Vec::new(), immovable: Vec::new() };
+	for &id in &game.ship_id_by_player[game.my_pid] {
+		if Some(id) != builder {
+			let ship = game.ships.get(&id).unwrap();
+			if game.move_cost_of(ship) as isize > ship.halite {
+				lists.immovable.push(id);
+			} else {
+				lists.movable.push(id);
+			}
+		}
+	}
+	lists
+}
+
+// goals
+// =====
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Goal {
+	pos: Point,
+}
+
+fn miner_goal_cost(game: &Game, miner: &Ship, goal_pos: Point, goal_type: GoalType) -> f32 {
+	let goal_halite = game.mining_value(goal_pos);
+	let movement_cost = game.move_cost_of(miner) as isize;
+	let cost_of_stopping_mining = if goal_pos != miner.pos && goal_type == GoalType::Mine {
+			if game.halite_at(miner.pos) > game.constants.min_mining_halite() {
+				10000.0*movement_cost as f32
+			} else { game.halite_at(miner.pos) as f32*0.23 }
+		}
+		else {0.0};
+	let net_halite = goal_halite*0.8
+		// + game.tiles_at_dist(goal_pos, 1).iter().map(|&pt| game.halite_at(pt)).sum::<usize>() as f32*0.2
+		// + game.tiles_at_dist(goal_pos, 2).iter().map(|&pt| game.halite_at(pt)).sum::<usize>() as f32*0.05
+		- cost_of_stopping_mining
+		- game.halite_between2(miner.pos, goal_pos, &|&x| x) as f32/game.constants.move_cost_ratio as f32
+		- game.home.halite(goal_pos) as f32*0.2;
+	let net_turns: f32 = 1.2*game.dist(miner.pos, goal_pos) as f32 + 0.9*game.home.turns(goal_pos) as f32 + 1.0;
+	net_halite/net_turns.powf(1.4)
+}
+
+fn pick_goals(game: &Game, my_miners: &[usize], min_num_goals: usize, dist_map: &GMap<usize>) -> Vec<Goal> {
+	let mut out: Vec<Goal> = Vec::new();
+
+	let mut goal_order = BinaryHeap::new();
+
+	for x in 0..game.width {
+		for y in 0..game.height {
+			let cell_pos = Point{x: x as isize, y: y as isize,};
+			if !game.my_drop_pts.contains(&cell_pos) {
+				let efficiency = game.mining_value(cell_pos)/(1.0 + *dist_map.get(cell_pos) as f32);
+				let rounded_efficiency = (1000000.0*efficiency) as isize;
+				goal_order.push(CellPriorityMax {
+					pos: cell_pos,
+					w: rounded_efficiency,
+				});
+			}
+		}
+	}
+
+	for id in my_miners {
+		let s = game.ships.get(id).unwrap();
+		out.push(Goal{pos: s.pos}); // maybe add neighborhood? but too many goals...
+	}
+	// add the first min_num_goals from goal_order into out
+	while let Some(g) = goal_order.pop() {
+		if out.len() >= min_num_goals {
+			break;
+		}
+		let goal = Goal{pos: g.pos};
+		if !out.contains(&goal) {
+			out.push(goal);
+			if game.turn_number.is_multiple_of(2) {
+				warn!("{{\"t\": {}, \"x\": {}, \"y\": {}, \"msg\": \"goal\", \"color\": \"{}\"}},",
+						game.turn_number, g.pos.x, g.pos.y,
+						"#990099");
+			}
+		}
+	}
+
+	// if there are not enough in goal_order, add copies of the latest dropoff
+	while out.len() < min_num_goals {
+		out.push(Goal{pos: game.my_drop_pts[game.my_drop_pts.len()-1]});
+	}
+	out
+}
+
+// What each miner past a cluster's capacity costs, as a share of the best miner_goal_cost
+// any miner has for one of its goals (about what that miner would make there per turn):
+// the first extra miner pays this, the second twice this, and so on. Miners that fit are free.
+const CLUSTER_FILL_SHARE: f64 = 0.5;
+
+// Groups goals that are at most 2 apart. A cluster can take one miner for every
+// half a ship's worth of halite on and right next to its tiles (at least one).
+// Returns the cluster of each goal and the capacity of each cluster.
+fn goal_clusters(game: &Game, goals: &[Goal]) -> (Vec<usize>, Vec<usize>) {
+	let mut ds = DisjointSet::make_singletons(goals.len());
+	for i in 0..goals.len() {
+		for j in (i+1)..goals.len() {
+			if game.dist(goals[i].pos, goals[j].pos) <= 2 {
+				ds.unite(i, j);
+			}
+		}
+	}
+
+	let mut cluster_of_root: HashMap<usize, usize> = HashMap::new();
+	let mut cluster_of_goal = Vec::with_capacity(goals.len());
+	let mut tiles: Vec<HashSet<Point>> = Vec::new();
+	for (i, goal) in goals.iter().enumerate() {
+		let root = ds.find(i);
+		let next = cluster_of_root.len();
+		let k = *cluster_of_root.entry(root).or_insert(next);
+		if k == tiles.len() {
+			tiles.push(HashSet::new());
+		}
+		tiles[k].extend(game.tiles_within_dist(goal.pos, 1));
+		cluster_of_goal.push(k);
+	}
+
+	let capacity = tiles.iter()
+		.map(|t| {
+			let halite: usize = t.iter().map(|&p| game.halite_at(p)).sum();
+			(halite / (game.constants.max_halite/2)).max(1)
+		})
+		.collect();
+	(cluster_of_goal, capacity)
+}
+
+// keeps each ship's GoalType and hands out the tile each ship heads for
+pub struct GoalAssigner {
+	pub goal_types: HashMap<usize, GoalType>,
+	pub endgame: bool,
+	pub method: AssignMethod,
+}
+
+pub struct Assignment {
+	pub targets: HashMap<usize, Point>,
+	pub mining_goals: Vec<(usize, Point)>, // miners that set off for tiles away from my drop points, in the order they were picked
+}
+
+impl Assignment {
+	// sends a ship somewhere else than the goal it was assigned, which is then no longer a
+	// tile miners are heading for
+	pub fn retarget(&mut self, id: usize, pos: Point) {
+		self.targets.insert(id, pos);
+		self.mining_goals.retain(|&(ship_id, _)| ship_id != id);
+	}
+}
+
+impl GoalAssigner {
+	pub fn new() -> GoalAssigner {
+		GoalAssigner {
+			goal_types: HashMap::new(),
+			endgame: false,
+			method: AssignMethod::Greedy,
+		}
+	}
+
+	// switches ships between mining and depositing, and starts the endgame when it is time to cash out
+	pub fn update_goal_types(&mut self, game: &Game, ships: &ShipLists, endgame_margin: usize) {
+		let my_ships_ids = &game.ship_id_by_player[game.my_pid];
+		let turns_left = game.constants.max_turns - game.turn_number;
+		self.goal_types.retain(|&id, _| my_ships_ids.contains(&id));
+		for &id in my_ships_ids {
+			if ships.movable.contains(&id) || ships.immovable.contains(&id) {
+				self.goal_types.entry(id).or_insert(GoalType::Mine); // new ship?
+			}
+		}
+
+		for id in &ships.movable {
+			let ship = game.ships.get(id).unwrap();
+			let dist_to_drop = game.my_drop_field.dist(ship.pos);
+			let endgame_collect = dist_to_drop+endgame_margin >= turns_left;
+			if endgame_collect && !self.endgame {
+				self.endgame = true;
+				info!("endgame: begin cashing out all ships");
+			}
+
+			match *self.goal_types.get(id).unwrap() {
+				GoalType::Deposit => {
+					if game.my_drop_pts.contains(&ship.pos) && !self.endgame {
+						self.goal_types.insert(*id, GoalType::TowardsMine);
+					}
+				},
+				GoalType::TowardsMine => {
+					if ship.halite >= game.constants.return_cargo() as isize || endgame_collect {
+						self.goal_types.insert(*id, GoalType::Deposit);
+					} else if game.halite_at(ship.pos) > game.constants.min_mining_halite() {
+						self.goal_types.insert(*id, GoalType::Mine);
+					}
+				},
+				GoalType::Mine => {
+					if ship.halite >= game.constants.return_cargo() as isize || endgame_collect {
+						self.goal_types.insert(*id, GoalType::Deposit);
+					} else if game.halite_at(ship.pos) <= game.constants.min_mining_halite() {
+						self.goal_types.insert(*id, GoalType::TowardsMine);
+					}
+				},
+				GoalType::Attack => { // attacks are planned afresh every turn
+					if ship.halite >= game.constants.return_cargo() as isize || endgame_collect {
+						self.goal_types.insert(*id, GoalType::Deposit);
+					} else {
+						self.goal_types.insert(*id, GoalType::TowardsMine);
+					}
+				},
+			}
+		}
+	}
+
+	// miners get the goal tiles that maximize the total miner_goal_cost, greedily or optimally
+	// depending on method; depositing ships head for the nearest drop point
+	pub fn assign(&mut self, game: &Game, movable: &[usize]) -> Assignment {
+		let mut targets: HashMap<usize, Point> = HashMap::new();
+		let mut mining_goals: Vec<(usize, Point)> = Vec::new();
+
+		let my_miners: Vec<usize> = movable.iter()
+			.filter(|id| *self.goal_types.get(id).unwrap() != GoalType::Deposit)
+			.cloned()
+			.collect();
+
+		// list the most efficient squares for mining
+		let num_ships = game.ship_id_by_player[game.my_pid].len();
+		let picked_goals = pick_goals(game, &my_miners, 4*num_ships+20, &game.my_drop_field.dist);
+
+		// assign miners to objectives
+		info!("Miners: {:?}", my_miners);
+
+		if !my_miners.is_empty() {
+			// make table, with my_miners as the rows and picked_goals as the columns
+			let weights: Vec<Vec<f32>> = my_miners.iter()
+				.map(|id| {
+					let ship = game.ships.get(id).unwrap();
+					let goal_type = *self.goal_types.get(id).unwrap();
+					picked_goals.iter()
+						.map(|goal| miner_goal_cost(game, ship, goal.pos, goal_type))
+						.collect()
+				})
+				.collect();
+
+			let greedy = assignment::greedy(&weights);
+			let optimal = assignment::hungarian(&weights);
+			info!("assignment objective: greedy {:.2}, optimal {:.2}",
+				assignment::total_weight(&weights, &greedy), assignment::total_weight(&weights, &optimal));
+			let picked = match self.method {
+				AssignMethod::Greedy => greedy,
+				AssignMethod::Hungarian => optimal,
+				AssignMethod::MinCostFlow => {
+					let (cluster_of_goal, capacity) = goal_clusters(game, &picked_goals);
+					let mut fill_cost = vec![0.0; capacity.len()];
+					for row in &weights {
+						for (j, &w) in row.iter().enumerate() {
+							let k = cluster_of_goal[j];
+							fill_cost[k] = f64::max(fill_cost[k], CLUSTER_FILL_SHARE*w as f64);
+						}
+					}
+					let flow = assignment::min_cost_flow(&weights, &cluster_of_goal, &capacity, &fill_cost);
+					info!("assignment objective: flow {:.2} over {} clusters",
+						assignment::total_weight(&weights, &flow), capacity.len());
+					flow
+				},
+			};
+
+			// heaviest pairs first, which is also the order the greedy method picks them in
+			let mut pairs: Vec<(usize, usize)> = picked.iter().enumerate()
+				.filter_map(|(i, j)| j.map(|j| (i, j)))
+				.collect();
+			pairs.sort_by(|&(i1, j1), &(i2, j2)| weights[i2][j2].partial_cmp(&weights[i1][j1]).unwrap()
+				.then(j1.cmp(&j2)).then(i1.cmp(&i2)));
+
+			for (i, j) in pairs {
+				let goal_pos = picked_goals[j].pos;
+				let id = my_miners[i];
+				let ship = game.ships.get(&id).unwrap();
+
+				info!("ship {}({}, {}) -> ({}, {})",
+					ship.ship_id, ship.pos.x, ship.pos.y, goal_pos.x, goal_pos.y);
+				warn!("{{\"t\": {}, \"x\": {}, \"y\": {}, \"msg\": \"ship {}\", \"color\": \"{}\"}},",
+					game.turn_number, goal_pos.x, goal_pos.y, id,
+					if ship.pos == goal_pos { "#0000DD" } else { "#00DD00" });
+				targets.insert(id, goal_pos);
+				self.goal_types.insert(id,
+					if ship.pos == goal_pos { GoalType::Mine } else { GoalType::TowardsMine });
+				if ship.pos != goal_pos && !game.my_drop_pts.contains(&goal_pos) {
+					mining_goals.push((id, goal_pos));
+				}
+			}
+		}
+
+		for id in movable {
+			if *self.goal_types.get(id).unwrap() == GoalType::Deposit {
+				let ship = game.ships.get(id).unwrap();
+				targets.insert(*id, game.home.home(ship.pos).unwrap()); // go home
+				info!("ship {} at ({},{}) going back to base", id, ship.pos.x, ship.pos.y);
+			}
+		}
+		Assignment { targets, mining_goals }
+	}
+}
+
+// combat
+// ======
+
+// only worth it in 2 player games: with more players, whoever stays out of the collision
+// gains the most from it
+const ATTACK_MIN_CARGO: isize = 500; // enemy ships carrying less are not worth a ship
+const ATTACK_MAX_CARGO: isize = 100; // attackers should have next to nothing to lose
+const ATTACK_RADIUS: usize = 8; // how far from my nearest drop point a collision may be
+const SUPPORT_RADIUS: usize = 4; // ships this close to the collision race for the dropped cargo
+const ATTACK_MIN_GAIN: f32 = 300.0;
+
+// What ramming target with attacker gains me over the other player. Both lose a ship and
+// both cargos are dropped on the tile, where each player's share is guessed from the
+// ships either has close by.
+fn attack_gain(game: &Game, attacker: &Ship, target: &Ship, mine: usize, theirs: usize) -> f32 {
+	let share = (mine as f32 + 1.0)/((mine + theirs) as f32 + 2.0);
+	let dropped = (attacker.halite + target.halite) as f32;
+	(2.0*share - 1.0)*dropped + (target.halite - attacker.halite) as f32
+		- game.move_cost_of(attacker) as f32
+}
+
+// a ship of mine to move onto the tile of a loaded enemy ship next to it
+pub struct Attack {
+	pub attacker: usize,
+	pub target: usize,
+	pub pos: Point,
+}
+
+// picks collisions worth making: nearly empty ships of mine next to loaded enemy ships,
+// closer to my drop points than to theirs and where I have more ships around
+pub struct CombatPlanner<'a> {
+	pub game: &'a Game,
+	pub goal_types: &'a HashMap<usize, GoalType>,
+	pub opponents: &'a OpponentTracker,
+}
+
+impl<'a> CombatPlanner<'a> {
+	pub fn plan(&self, movable: &[usize]) -> Vec<Attack> {
+		let game = self.game;
+		let mut options: Vec<(f32, usize, usize)> = Vec::new();
+		for &id in movable {
+			let ship = game.ships.get(&id).unwrap();
+			if *self.goal_types.get(&id).unwrap() == GoalType::Deposit || ship.halite > ATTACK_MAX_CARGO {
+				continue;
+			}
+			for pos in game.neighbors(ship.pos) {
+				let target = match game.ship_map.get(&pos) {
+					Some(t_id) if game.ships[t_id].player != game.my_pid => &game.ships[t_id],
+					_ => continue,
+				};
+				let home_dist = game.my_drop_field.dist(pos);
+				let enemy_home_dist = game.enemy_drop_pts.keys().map(|&d| game.dist(pos, d)).min().unwrap_or(game.max_dist());
+				if target.halite < ATTACK_MIN_CARGO || home_dist > ATTACK_RADIUS || home_dist >= enemy_home_dist {
+					continue;
+				}
+
+				let (mut mine, mut theirs) = (0, 0);
+				for p in game.tiles_within_dist(pos, SUPPORT_RADIUS) {
+					if let Some(&o_id) = game.ship_map.get(&p) {
+						if o_id == id || o_id == target.ship_id {
+							continue;
+						}
+						if game.ships[&o_id].player == game.my_pid {
+							mine += 1;
+						} else {
+							theirs += 1;
+						}
+					}
+				}
+				// players that often step away make attacks on them less likely to land
+				let gain = attack_gain(game, ship, target, mine, theirs)
+					* self.opponents.profile(target.player).contact_risk() as f32;
+				if mine > theirs && gain >= ATTACK_MIN_GAIN {
+					options.push((gain, id, target.ship_id));
+				}
+			}
+		}
+
+		// best trades first, one attacker per target
+		options.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+		let mut attacks: Vec<Attack> = Vec::new();
+		for (gain, attacker, target) in options {
+			if attacks.iter().any(|a| a.attacker == attacker || a.target == target) {
+				continue;
+			}
+			let pos = game.ships[&target].pos;
+			info!("ship {} attacking ship {} at ({}, {}), gain {:.0}", attacker, target, pos.x, pos.y, gain);
+			attacks.push(Attack { attacker, target, pos });
+		}
+		attacks
+	}
+}
+
+// moves
+// =====
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShipPriority {
+    pub id: usize,
+    pub w: isize,
+}
+
+impl Ord for ShipPriority {
+    fn cmp(&self, other: &ShipPriority) -> Ordering {
+        other.w.cmp(&self.w) // so that smaller numbers go first
+    }
+}
+
+impl PartialOrd for ShipPriority {
+    fn partial_cmp(&self, other: &ShipPriority) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// movable ships, the ones that should pick their move first on top
+fn move_order(game: &Game, goal_types: &HashMap<usize, GoalType>, movable: &[usize],
+	targets: &HashMap<usize, Point>) -> BinaryHeap<ShipPriority> {
+	let mut move_order = BinaryHeap::new();
+	for id in movable {
+		let ship = game.ships.get(id).unwrap();
+		let drop_pos = game.nearest_drop_pos(ship.pos);
+		let goal_type = *goal_types.get(id).unwrap();
+		let mut priority = 3*game.dist(ship.pos, drop_pos) as isize;
+		if ship.pos == drop_pos {
+			priority -= 300;
+		}
+		if goal_type == GoalType::Deposit {
+			priority -= 300;
+			priority -= ship.halite/10;
+		} else if goal_type == GoalType::Mine {
+			priority -= 150;
+			priority -= game.halite_at(ship.pos) as isize/20;
+		} else if goal_type == GoalType::TowardsMine {
+			priority -= game.halite_at(ship.pos) as isize/20;
+			priority += game.dist(ship.pos, *targets.get(id).unwrap()) as isize;
+		} else if goal_type == GoalType::Attack {
+			priority -= 400; // before the target can be blocked by my own ships
+		}
+		move_order.push(ShipPriority{
+				id: *id,
+				w: priority,
+			});
+	}
+	move_order
+}
+
+// In 4 player games, how badly ship should avoid pos given the enemy forecast, in turns of
+// detour. Ships close to a drop point, nearly empty ships late in the game and ships that
+// have been stuck for a while care less. Tiles next to players that rarely back off count
+// more and tiles next to players that usually do count less, by the mean over the enemy
+// ships around.
+fn enemy_risk(game: &Game, enemy_forecast: &HashMap<Point, usize>, opponents: &OpponentTracker,
+	stuck: &StuckTracker, ship: &Ship, pos: Point) -> Option<f32> {
+	if game.num_players != 4 {
+		return None;
+	}
+	let forecast = *enemy_forecast.get(&pos)?;
+	let dist_to_dropoff = game.my_drop_field.dist(ship.pos);
+	let mut factor: f32 = forecast as f32 * dropoff_proximity(dist_to_dropoff) *
+		ship_val(game.turn_number as f32/game.constants.max_turns as f32, ship.halite as f32/game.constants.max_halite as f32);
+	if let Some(&turns) = stuck.turns_stuck.get(&ship.ship_id) {
+		factor *= 0.87_f32.powf(turns as f32);
+	}
+	let risks: Vec<f64> = game.neighborhood(pos).iter()
+		.filter_map(|p| game.ship_map.get(p))
+		.map(|id| game.ships[id].player)
+		.filter(|&pid| pid != game.my_pid)
+		.map(|pid| opponents.profile(pid).contact_risk())
+		.collect();
+	let contact_risk = if risks.is_empty() { 1.0 } else { risks.iter().sum::<f64>()/risks.len() as f64 };
+	Some(factor*contact_risk as f32)
+}
+
+// picks a move for each movable ship in priority order, avoiding tiles already taken
+pub struct MovePlanner<'a> {
+	pub game: &'a Game,
+	pub goal_types: &'a HashMap<usize, GoalType>,
+	pub endgame: bool,
+	pub stuck: &'a StuckTracker,
+	pub enemy_forecast: &'a HashMap<Point, usize>, // percent chance an enemy ship ends up on a tile
+	pub opponents: &'a OpponentTracker,
+}
+
+// what the move planner decided, and what the collision resolver needs to undo some of it
+pub struct MovePlan {
+	pub commands: HashMap<usize, ShipCommand>,
+	pub forbidden: HashSet<Point>, // tiles my ships will occupy next turn
+	pub immovable_pos: HashSet<Point>,
+	pub movable_next: HashMap<Point, usize>, // where my movable ships end up
+	pub colliding: BTreeSet<usize>, // ships that found no free tile
+	pub move_scores: HashMap<usize, Vec<(Point, Direction, f32)>>,
+	pub o_directions: HashMap<usize, Direction>, // where does the ship want to go, if self-collisions with others of my movable ships were not a problem?
+}
+
+impl<'a> MovePlanner<'a> {
+	pub fn plan(&self, ships: &ShipLists, targets: &HashMap<usize, Point>) -> MovePlan {
+		let game = self.game;
+		let mut commands: HashMap<usize, ShipCommand> = HashMap::new();
+		for &id in &ships.immovable {
+			commands.insert(id, ShipCommand::MoveShip(Direction::Still));
+		}
+
+		let mut move_order = move_order(game, self.goal_types, &ships.movable, targets);
+
+		let immovable_pos: HashSet<Point> = ships.immovable.iter()
+											.map(|&id| game.ships.get(&id).unwrap().pos)
+											.collect();
+		let mut forbidden: HashSet<Point> = HashSet::from_iter(immovable_pos.iter().cloned());
+		if self.endgame {
+			forbidden.retain(|&p| !game.my_drop_pts.contains(&p));
+		}
+
+		let mut movable_next: HashMap<Point, usize> = HashMap::new();
+		let mut colliding: BTreeSet<usize> = BTreeSet::new();
+
+		let mut move_scores: HashMap<usize, Vec<(Point, Direction, f32)>> = HashMap::new();
+		let mut o_directions: HashMap<usize, Direction> = HashMap::new();
+		let k: isize = 100000;
+		let mut search = GridSearch::new(game.width, game.height);
+		while let Some(ShipPriority{ id, w: _ }) = move_order.pop() {
+			let ship = game.ships.get(&id).unwrap();
+			let target = *targets.get(&id).unwrap();
+			// cheapest way from each of the five cells around the ship to the target
+			let neighborhood = game.neighborhood(ship.pos);
+			let going_home = game.home.home(ship.pos) == Some(target);
+			let nav_scores: Vec<(Point, Direction, isize)> = if going_home {
+				// going home: the home field's first step costs what the whole route does, at
+				// TURN_COST a turn plus the halite burned; waiting adds a turn and any other step
+				// pays for itself on top of the route from where it leads. Scaled so that a turn
+				// counts k as in the search below.
+				let route_cost = game.home.cost.dist(ship.pos);
+				let first_step = game.home.first_step(ship.pos);
+				neighborhood.iter().zip(Direction::all_directions())
+					.map(|(&pos, d)| {
+						let cost = if d == first_step {
+							route_cost
+						} else if d == Direction::Still {
+							TURN_COST as usize + route_cost
+						} else {
+							TURN_COST as usize + game.move_cost_of(ship) + game.home.cost.dist(pos)
+						};
+						(pos, d, k/TURN_COST*cost as isize)
+					})
+					.collect()
+			} else {
+				search.run(game, target, ship.pos, &neighborhood, k, &|_, next| k + game.halite_at(next) as isize);
+				neighborhood.iter().zip(Direction::all_directions())
+					.map(|(&pos, d)| (pos, d, search.cost(pos).unwrap()))
+					.collect()
+			};
+
+			let mut best_score = 1000000000000.0;
+			let mut direction = Direction::Still;
+			let mut best_pos = ship.pos;
+
+			let mut o_best_score = best_score;
+			let mut o_direction = Direction::Still;
+
+			let mut forbidden_count = 0;
+			let mut ship_move_scores: Vec<(Point, Direction, f32)> = Vec::new();
+			for (pos, d, nscore) in nav_scores {
+				let mut score = nscore as f32;
+				let mut o_score = 0.0;
+				if forbidden.contains(&pos) {
+					score += k as f32*1000.0;
+					if !immovable_pos.contains(&pos) {
+						o_score -= k as f32*1000.0; // don't care about self-collisions with others of my movable ships
+					}
+					forbidden_count += 1;
+				} else if let Some(factor) = enemy_risk(game, self.enemy_forecast, self.opponents, self.stuck, ship, pos) {
+					score += k as f32 * factor;
+				}
+				if ship.pos == target {
+					score -= 4.0*game.halite_at(pos) as f32;
+				}
+				o_score += score;
+				ship_move_scores.push((pos, d, o_score));
+
+				if score < best_score {
+					best_score = score;
+					direction = d;
+					best_pos = pos;
+				}
+				if o_score < o_best_score {
+					o_best_score = o_score;
+					o_direction = d;
+				}
+			}
+			move_scores.insert(id, ship_move_scores);
+			o_directions.insert(id, o_direction);
+
+			let is_colliding = forbidden_count == 5;
+			if is_colliding {
+				colliding.insert(id);
+			} else {
+				if !game.my_drop_pts.contains(&best_pos) || !self.endgame {
+					forbidden.insert(best_pos);
+					movable_next.insert(best_pos, id);
+				}
+				commands.insert(id, ShipCommand::MoveShip(direction));
+			}
+			if log_enabled!(::log::Level::Debug) && !going_home {
+				if let Some(path) = search.path(best_pos) {
+					debug!("ship {} route to ({}, {}): {} steps", id, target.x, target.y, path.len()-1);
+				}
+			}
+		}
+
+		MovePlan { commands, forbidden, immovable_pos, movable_next, colliding, move_scores, o_directions }
+	}
+}
+
+// Same job as MovePlanner, but each ship plans a path several turns ahead with
+// cooperative::plan_path and reserves it, so later ships route around earlier ones.
+pub struct CooperativeMovePlanner<'a> {
+	pub game: &'a Game,
+	pub goal_types: &'a HashMap<usize, GoalType>,
+	pub endgame: bool,
+	pub stuck: &'a StuckTracker,
+	pub enemy_forecast: &'a HashMap<Point, usize>,
+	pub opponents: &'a OpponentTracker,
+	pub window: usize,
+}
+
+impl<'a> CooperativeMovePlanner<'a> {
+	// also returns the reservation table, which holds every ship's planned path
+	pub fn plan(&self, ships: &ShipLists, targets: &HashMap<usize, Point>) -> (MovePlan, ReservationTable) {
+		let game = self.game;
+		let mut table = ReservationTable::new(self.window);
+		if self.endgame {
+			for &p in &game.my_drop_pts {
+				table.share(p);
+			}
+		}
+
+		let mut commands: HashMap<usize, ShipCommand> = HashMap::new();
+		for &id in &ships.immovable {
+			commands.insert(id, ShipCommand::MoveShip(Direction::Still));
+			table.reserve_path(id, vec![game.ships.get(&id).unwrap().pos], true);
+		}
+		let immovable_pos: HashSet<Point> = ships.immovable.iter()
+											.map(|&id| game.ships.get(&id).unwrap().pos)
+											.collect();
+		let mut forbidden: HashSet<Point> = immovable_pos.iter().cloned()
+			.filter(|&p| !self.endgame || !game.my_drop_pts.contains(&p))
+			.collect();
+
+		let mut movable_next: HashMap<Point, usize> = HashMap::new();
+		let mut colliding: BTreeSet<usize> = BTreeSet::new();
+		let mut move_scores: HashMap<usize, Vec<(Point, Direction, f32)>> = HashMap::new();
+		let mut o_directions: HashMap<usize, Direction> = HashMap::new();
+
+		let mut move_order = move_order(game, self.goal_types, &ships.movable, targets);
+		while let Some(ShipPriority{ id, w: _ }) = move_order.pop() {
+			let ship = game.ships.get(&id).unwrap();
+			let target = *targets.get(&id).unwrap();
+
+			// one step scores that ignore my other movable ships, for the collision resolver
+			let mut ship_move_scores: Vec<(Point, Direction, f32)> = Vec::new();
+			for d in Direction::all_directions() {
+				let pos = game.step_toward(ship.pos, d);
+				let mut score = (TURN_COST*game.dist(pos, target) as isize) as f32;
+				if d != Direction::Still {
+					score += game.move_cost_of(ship) as f32;
+				}
+				if immovable_pos.contains(&pos) {
+					score += (TURN_COST*1000) as f32;
+				}
+				ship_move_scores.push((pos, d, score));
+			}
+			let o_direction = ship_move_scores.iter()
+				.fold((Direction::Still, f32::MAX), |best, &(_, d, score)| if score < best.1 { (d, score) } else { best })
+				.0;
+			move_scores.insert(id, ship_move_scores);
+			o_directions.insert(id, o_direction);
+
+			let step_cost = |pos: Point, turn: usize| {
+				if turn != 1 {
+					return 0;
+				}
+				enemy_risk(game, self.enemy_forecast, self.opponents, self.stuck, ship, pos)
+					.map_or(0, |factor| (TURN_COST as f32*factor) as isize)
+			};
+			// ships leave a drop point right after unloading, miners stay on their tile
+			let stay_at_goal = !game.my_drop_pts.contains(&target);
+			match plan_path(game, &table, ship.pos, ship.halite as usize, target, stay_at_goal, &step_cost) {
+				Some(path) => {
+					let next = path[1];
+					let direction = Direction::all_directions().into_iter()
+						.find(|&d| game.step_toward(ship.pos, d) == next)
+						.unwrap();
+					if !game.my_drop_pts.contains(&next) || !self.endgame {
+						forbidden.insert(next);
+						movable_next.insert(next, id);
+					}
+					commands.insert(id, ShipCommand::MoveShip(direction));
+					table.reserve_path(id, path, stay_at_goal);
+				},
+				None => {
+					colliding.insert(id);
+				},
+			}
+		}
+
+		let plan = MovePlan { commands, forbidden, immovable_pos, movable_next, colliding, move_scores, o_directions };
+		(plan, table)
+	}
+}
+
+// collisions
+// ==========
+
+// reroutes ships the move planner left without a free tile by bumping the fewest other ships
+pub struct CollisionResolver<'a> {
+	pub game: &'a Game,
+	pub endgame: bool,
+}
+
+impl<'a> CollisionResolver<'a> {
+	pub fn resolve(&self, plan: &mut MovePlan) {
+		let game = self.game;
+		let colliding = ::std::mem::take(&mut plan.colliding);
+		for id in colliding {
+			info!("ship {} self-colliding: {:?}", id, plan.o_directions.get(&id).unwrap());
+
+			// update commands, movable_next
+			let mut best_dir_list: Vec<(usize, Point, Direction)> = Vec::new();
+			let mut best_dir = Direction::Still;
+			let mut best_num_ships_rerouted = 10000;
+			let mut best_diff_oscore = 1.0e20;
+			let mut best_new_pos = Point{ x:0, y:0 };
+			for &(pos, dir, score) in plan.move_scores.get(&id).unwrap() {
+				let rmc = resolve_movable_chain(game, game.ships.get(&id).unwrap(), pos, &plan.movable_next,
+					&plan.immovable_pos, &plan.move_scores, &plan.commands);
+				info!("{:?}", rmc);
+				if let Some((mut d_oscore, dir_list)) = rmc {
+					let mut num_ships = dir_list.len();
+					if dir != *plan.o_directions.get(&id).unwrap() {
+						num_ships += 1;
+					}
+					d_oscore += score;
+					if num_ships < best_num_ships_rerouted ||
+						(num_ships == best_num_ships_rerouted && d_oscore < best_diff_oscore) {
+						best_dir_list = dir_list;
+						best_dir = dir;
+						best_num_ships_rerouted = num_ships;
+						best_diff_oscore = d_oscore;
+						best_new_pos = pos;
+					}
+				}
+			}
+
+			if best_num_ships_rerouted < 10000 {
+				info!("rerouting: {:?}, {:?}", best_dir, best_dir_list);
+				for &(_, prev_pos, _) in &best_dir_list {
+					plan.forbidden.remove(&prev_pos);
+					plan.movable_next.remove(&prev_pos);
+				}
+				plan.commands.insert(id, ShipCommand::MoveShip(best_dir));
+				if !game.my_drop_pts.contains(&best_new_pos) || !self.endgame {
+					plan.forbidden.insert(best_new_pos);
+					plan.movable_next.insert(best_new_pos, id);
+				}
+
+				for (oid, _, dir) in best_dir_list {
+					let new_pos = game.step_toward(game.ships.get(&oid).unwrap().pos, dir);
+					plan.commands.insert(oid, ShipCommand::MoveShip(dir));
+					if !game.my_drop_pts.contains(&new_pos) || !self.endgame {
+						plan.forbidden.insert(new_pos);
+						plan.movable_next.insert(new_pos, oid);
+					}
+				}
+			} else {
+				info!("failed to re-route ship {}", id);
+			}
+		}
+	}
+}
+
+// change in score, and the (ship, tile it gives up, new direction) of every ship bumped
+pub type Reroute = (f32, Vec<(usize, Point, Direction)>);
+
+// follows the chain of my ships pushed out of the way when c_ship moves to pos
+pub fn resolve_movable_chain(game: &Game, c_ship: &Ship, pos: Point, o_movable_next: &HashMap<Point, usize>,
+	immovable_pos: &HashSet<Point>, move_scores: &HashMap<usize, Vec<(Point, Direction, f32)>>,
+	commands: &HashMap<usize, ShipCommand>) -> Option<Reroute> {
+
+	if immovable_pos.contains(&pos) {
+		return None;
+	}
+	let mut movable_next = o_movable_next.clone();
+
+	let mut prev_id = c_ship.ship_id;
+	let mut prev_pos = pos;
+
+	let mut new_commands: Vec<(usize, Point, Direction)> = Vec::new();
+	let mut d_oscore: f32 = 0.0;
+
+	loop {
+		if immovable_pos.contains(&prev_pos) {
+			info!("uh-oh - immovable_pos contains ({},{}), {:?}", prev_pos.x, prev_pos.y, new_commands);
+			return None;
+		}
+		match movable_next.get(&prev_pos) {
+			Some(&id) => {
+				let curr_id = id;
+				let curr_ship = game.ships.get(&id).unwrap();
+				let curr_pos = curr_ship.pos;
+				let curr_dir = match commands.get(&id) {
+					Some(&ShipCommand::MoveShip(dir)) => dir,
+					_ => {info!("invalid command found for ship {}", id); return None},
+				};
+
+				movable_next.insert(prev_pos, prev_id); // overwrites what was prev_pos => curr_id
+
+				// need to handle endgame when it is okay to collide on drop point
+
+				// see if curr_ship could go somewhere free
+				let mut best_oscore = f32::MAX;
+				let mut prev_oscore = f32::MAX;
+				let mut still_oscore = f32::MAX;
+				let mut best_dir = curr_dir;
+				for &(new_pos, dir, o_score) in move_scores.get(&id).unwrap() {
+					if !immovable_pos.contains(&new_pos) && !movable_next.contains_key(&new_pos)
+						&& o_score < best_oscore {
+							best_oscore = o_score;
+							best_dir = dir;
+						}
+					if dir == curr_dir {
+						prev_oscore = o_score;
+					}
+					if dir == Direction::Still {
+						still_oscore = o_score;
+					}
+				}
+
+				if best_oscore != f32::MAX {
+					new_commands.push((curr_id, prev_pos, best_dir));
+					d_oscore += best_oscore - prev_oscore;
+					return Some((d_oscore, new_commands));
+				} else {
+				// if curr_ship cannot go anywhere else free...
+				// if it was planning to be still, give up
+				// if it was planning to move, plan to be still and bump the ship moving in (next iteration of loop)
+					if curr_dir == Direction::Still {
+						return None;
+					} else {
+						new_commands.push((curr_id, prev_pos, Direction::Still));
+						d_oscore += still_oscore - prev_oscore;
+					}
+					prev_pos = curr_pos;
+					prev_id = curr_id;
+				}
+			},
+			None => return Some((d_oscore, new_commands)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use engine::Engine;
+
+	fn no_commands(engine: &Engine) -> Vec<(bool, HashMap<usize, ShipCommand>)> {
+		vec![(false, HashMap::new()); engine.num_players]
+	}
+
+	#[test]
+	fn stuck_tracker_counts_turns_still_next_to_an_enemy() {
+		let mut engine = Engine::flat(2, 8, 8, 0);
+		let stuck = engine.place_ship(0, 2, 1, 0);
+		let alone = engine.place_ship(0, 6, 6, 0); // 7 from the enemy
+		engine.place_ship(1, 3, 2, 0); // 2 from stuck
+		let mut game = engine.game_view(0);
+		let mut tracker = StuckTracker::new();
+		tracker.update(&game);
+		assert_eq!((tracker.turns_stuck[&stuck], tracker.turns_stuck[&alone]), (0, 0));
+
+		for turns in 1..3 {
+			engine.step(&no_commands(&engine));
+			engine.update_view(&mut game);
+			tracker.update(&game);
+			assert_eq!((tracker.turns_stuck[&stuck], tracker.turns_stuck[&alone]), (turns, 0));
+		}
+
+		// moving resets the count, and ships that are gone are forgotten
+		let mut commands = no_commands(&engine);
+		commands[0].1.insert(stuck, ShipCommand::MoveShip(Direction::West));
+		engine.ships.remove(&alone);
+		engine.step(&commands);
+		engine.update_view(&mut game);
+		tracker.update(&game);
+		assert_eq!(tracker.turns_stuck.get(&stuck), Some(&0));
+		assert_eq!(tracker.turns_stuck.get(&alone), None);
+	}
+
+	// Ship a at (2, 2) found no free tile: east is b's, staying is where c is heading.
+	// Every other tile is free. Scores are the planner's, lower is better.
+	fn boxed_in() -> (Game, MovePlan, [usize; 3]) {
+		let mut engine = Engine::flat(2, 8, 8, 0);
+		let a = engine.place_ship(0, 2, 2, 0);
+		let b = engine.place_ship(0, 3, 2, 0);
+		let c = engine.place_ship(0, 1, 2, 0);
+		let game = engine.game_view(0);
+		let at = |x, y| Point { x, y };
+		let plan = MovePlan {
+			commands: vec![(b, ShipCommand::MoveShip(Direction::Still)), (c, ShipCommand::MoveShip(Direction::East))]
+				.into_iter().collect(),
+			forbidden: vec![at(3, 2), at(2, 2)].into_iter().collect(),
+			immovable_pos: HashSet::new(),
+			movable_next: vec![(at(3, 2), b), (at(2, 2), c)].into_iter().collect(),
+			colliding: vec![a].into_iter().collect(),
+			move_scores: vec![
+				(a, vec![(at(3, 2), Direction::East, 1.0), (at(2, 2), Direction::Still, 2.0)]),
+				(b, vec![(at(3, 2), Direction::Still, 1.0), (at(3, 1), Direction::North, 3.0)]),
+				(c, vec![(at(2, 2), Direction::East, 1.0), (at(1, 2), Direction::Still, 2.0)]),
+			].into_iter().collect(),
+			o_directions: vec![(a, Direction::East), (b, Direction::Still), (c, Direction::East)].into_iter().collect(),
+		};
+		(game, plan, [a, b, c])
+	}
+
+	#[test]
+	fn collision_resolver_bumps_the_fewest_ships() {
+		let (game, mut plan, [a, b, c]) = boxed_in();
+		CollisionResolver { game: &game, endgame: false }.resolve(&mut plan);
+		// going east moves only b; staying would also turn a away from east
+		assert_eq!(plan.commands[&a], ShipCommand::MoveShip(Direction::East));
+		assert_eq!(plan.commands[&b], ShipCommand::MoveShip(Direction::North));
+		assert_eq!(plan.commands[&c], ShipCommand::MoveShip(Direction::East));
+		assert_eq!(plan.movable_next[&Point { x: 3, y: 2 }], a);
+		assert_eq!(plan.movable_next[&Point { x: 3, y: 1 }], b);
+		assert!(plan.forbidden.contains(&Point { x: 3, y: 1 }));
+		assert!(plan.colliding.is_empty());
+	}
+
+	#[test]
+	fn collision_resolver_never_moves_immovable_ships() {
+		let (game, mut plan, [a, b, c]) = boxed_in();
+		plan.immovable_pos.insert(Point { x: 3, y: 2 });
+		CollisionResolver { game: &game, endgame: false }.resolve(&mut plan);
+		assert_eq!(plan.commands[&a], ShipCommand::MoveShip(Direction::Still));
+		assert_eq!(plan.commands[&b], ShipCommand::MoveShip(Direction::Still));
+		assert_eq!(plan.commands[&c], ShipCommand::MoveShip(Direction::Still));
+		assert_eq!(plan.movable_next[&Point { x: 2, y: 2 }], a);
+		assert_eq!(plan.movable_next[&Point { x: 1, y: 2 }], c);
+	}
+
+	#[test]
+	fn retargeted_ships_leave_the_mining_goals() {
+		// an empty map but for a rich patch all three miners set off for
+		let mut engine = Engine::flat(2, 16, 16, 0);
+		for x in 9..12 {
+			for y in 9..12 {
+				*engine.halite_map.get_mut(Point { x, y }) = 800;
+			}
+		}
+		let miners = [engine.place_ship(0, 4, 2, 0), engine.place_ship(0, 13, 4, 0), engine.place_ship(0, 6, 14, 0)];
+		let game = engine.game_view(0);
+		let ships = split_ships(&game, None);
+		let mut goals = GoalAssigner::new();
+		goals.update_goal_types(&game, &ships, 0);
+		let mut assignment = goals.assign(&game, &ships.movable);
+		let mut heading: Vec<usize> = assignment.mining_goals.iter().map(|&(id, _)| id).collect();
+		heading.sort();
+		assert_eq!(heading, miners.to_vec());
+		for &(id, pos) in &assignment.mining_goals {
+			assert_eq!(assignment.targets[&id], pos);
+		}
+
+		assignment.retarget(miners[1], Point { x: 13, y: 5 });
+		assert_eq!(assignment.targets[&miners[1]], Point { x: 13, y: 5 });
+		let heading: Vec<usize> = assignment.mining_goals.iter().map(|&(id, _)| id).collect();
+		assert!(!heading.contains(&miners[1]), "an attacker's old goal still counts toward dropoff candidates");
+		assert_eq!(heading.len(), 2);
+	}
+}
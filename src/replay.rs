@@ -0,0 +1,358 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::io;
+use serde_json::Value;
+use serde_json::map::Map;
+use zstd;
+use data::*;
+use game::{Game, GMap, Constants};
+use engine::{Engine, Event};
+
+// State at the start of one turn of a recorded match, plus what every player
+// did during that turn. `turn_number` counts from 0 like `Game::turn_number`.
+pub struct ReplayFrame {
+    pub turn_number: usize,
+    pub halite_map: GMap<usize>,
+    pub ships: BTreeMap<usize, Ship>,
+    pub dropoffs: Vec<Dropoff>,
+    pub energy: Vec<usize>,
+    pub moves: Vec<TurnCommands>,
+}
+
+// An official Halite III .hlt replay, unpacked into per-turn frames.
+pub struct Replay {
+    pub constants: Constants,
+    pub num_players: usize,
+    pub player_names: Vec<String>,
+    pub factories: Vec<Factory>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+fn field<'a>(v: &'a Value, key: &str) -> Result<&'a Value, String> {
+    v.get(key).ok_or_else(|| format!("replay is missing \"{}\"", key))
+}
+
+fn num(v: &Value, key: &str) -> Result<usize, String> {
+    field(v, key)?.as_u64().map(|n| n as usize)
+        .ok_or_else(|| format!("replay field \"{}\" is not a number", key))
+}
+
+fn location(v: &Value) -> Result<Point, String> {
+    Ok(Point { x: num(v, "x")? as isize, y: num(v, "y")? as isize })
+}
+
+// per-player tables are keyed by the player id as a string
+fn per_player<'a>(v: &'a Value, key: &str, pid: usize) -> Option<&'a Value> {
+    v.get(key).and_then(|table| table.get(pid.to_string()))
+}
+
+fn parse_moves(moves: Option<&Value>) -> Result<TurnCommands, String> {
+    let mut spawn = false;
+    let mut commands = HashMap::new();
+    for m in moves.and_then(Value::as_array).into_iter().flatten() {
+        match field(m, "type")?.as_str() {
+            Some("g") => spawn = true,
+            Some("c") => {
+                commands.insert(num(m, "id")?, ShipCommand::MakeDropoff());
+            },
+            Some("m") => {
+                let dir = field(m, "direction")?.as_str()
+                    .and_then(|d| d.chars().next())
+                    .and_then(Direction::from_char_encoding)
+                    .ok_or_else(|| format!("bad move direction in {}", m))?;
+                commands.insert(num(m, "id")?, ShipCommand::MoveShip(dir));
+            },
+            _ => return Err(format!("unknown move {}", m)),
+        }
+    }
+    Ok((spawn, commands))
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let mut bytes = Vec::new();
+        File::open(path).and_then(|mut f| f.read_to_end(&mut bytes))
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let json = if bytes.first() == Some(&b'{') {
+            bytes
+        } else {
+            zstd::decode_all(&bytes[..]).map_err(|e| format!("{}: {}", path, e))?
+        };
+        let replay: Value = ::serde_json::from_slice(&json).map_err(|e| format!("{}: {}", path, e))?;
+        Replay::from_json(&replay)
+    }
+
+    // Each full frame holds the entities at the start of its turn, the moves and
+    // events of that turn, the cells changed by it and the energy at its end.
+    // Frame 0 is the pre-game state and has no moves.
+    pub fn from_json(replay: &Value) -> Result<Replay, String> {
+        let constants = Constants::from_json(field(replay, "GAME_CONSTANTS")?).map_err(|e| e.to_string())?;
+        let num_players = num(replay, "number_of_players")?;
+
+        let mut factories = Vec::new();
+        let mut player_names = Vec::new();
+        for p in field(replay, "players")?.as_array().ok_or("\"players\" is not a list")? {
+            factories.push(Factory {
+                player: num(p, "player_id")?,
+                pos: location(field(p, "factory_location")?)?,
+            });
+            player_names.push(p.get("name").and_then(Value::as_str).unwrap_or("").to_owned());
+        }
+        factories.sort_by_key(|f| f.player);
+
+        let production_map = field(replay, "production_map")?;
+        let grid = field(production_map, "grid")?.as_array().ok_or("\"grid\" is not a list")?;
+        let mut gmap = Vec::new();
+        for row in grid {
+            let mut cells = Vec::new();
+            for cell in row.as_array().ok_or("grid row is not a list")? {
+                cells.push(num(cell, "energy")?);
+            }
+            if gmap.first().is_some_and(|r: &Vec<usize>| r.len() != cells.len()) {
+                return Err("grid rows have different lengths".to_string());
+            }
+            gmap.push(cells);
+        }
+        let mut halite_map = GMap::from_rows(gmap);
+
+        let full_frames = field(replay, "full_frames")?.as_array().ok_or("\"full_frames\" is not a list")?;
+        let mut frames = Vec::new();
+        let mut dropoffs: Vec<Dropoff> = Vec::new();
+        let first_frame = full_frames.first().ok_or("replay has no frames")?;
+        let mut energy: Vec<usize> = (0..num_players)
+            .map(|pid| per_player(first_frame, "energy", pid).and_then(Value::as_u64).unwrap_or(0) as usize)
+            .collect();
+        for (i, frame) in full_frames.iter().enumerate() {
+            if i > 0 {
+                let mut ships = BTreeMap::new();
+                for pid in 0..num_players {
+                    if let Some(entities) = per_player(frame, "entities", pid).and_then(Value::as_object) {
+                        for (id, e) in entities {
+                            let ship_id = id.parse::<usize>().map_err(|_| format!("bad ship id {}", id))?;
+                            ships.insert(ship_id, Ship {
+                                player: pid,
+                                ship_id,
+                                pos: location(e)?,
+                                halite: num(e, "energy")? as isize,
+                            });
+                        }
+                    }
+                }
+                let mut moves = Vec::new();
+                for pid in 0..num_players {
+                    moves.push(parse_moves(per_player(frame, "moves", pid))?);
+                }
+                frames.push(ReplayFrame {
+                    turn_number: i - 1,
+                    halite_map: halite_map.clone(),
+                    ships,
+                    dropoffs: dropoffs.to_vec(),
+                    energy: energy.to_vec(),
+                    moves,
+                });
+            }
+
+            // bring the map, dropoffs and energy to the end of this turn
+            for cell in frame.get("cells").and_then(Value::as_array).into_iter().flatten() {
+                *halite_map.get_mut(location(cell)?) = num(cell, "production")?;
+            }
+            for event in frame.get("events").and_then(Value::as_array).into_iter().flatten() {
+                if event.get("type").and_then(Value::as_str) == Some("construct") {
+                    dropoffs.push(Dropoff {
+                        player: num(event, "owner_id")?,
+                        pos: location(field(event, "location")?)?,
+                    });
+                }
+            }
+            for (pid, e) in energy.iter_mut().enumerate() {
+                if let Some(n) = per_player(frame, "energy", pid).and_then(Value::as_u64) {
+                    *e = n as usize;
+                }
+            }
+        }
+
+        Ok(Replay { constants, num_players, player_names, factories, frames })
+    }
+
+    pub fn num_turns(&self) -> usize {
+        self.frames.len()
+    }
+
+    // the Game player `pid` would have seen at the start of `turn`
+    pub fn game_at(&self, turn: usize, pid: usize) -> Game {
+        let frame = &self.frames[turn];
+        let mut engine = Engine::new(self.constants.clone(), self.factories.to_vec(),
+            frame.halite_map.clone(), 0);
+        engine.turn_number = frame.turn_number;
+        engine.ships = frame.ships.clone();
+        engine.dropoffs = frame.dropoffs.to_vec();
+        engine.energy = frame.energy.to_vec();
+        engine.game_view(pid)
+    }
+
+    pub fn timeline(&self, pid: usize) -> Vec<Game> {
+        (0..self.num_turns()).map(|turn| self.game_at(turn, pid)).collect()
+    }
+}
+
+fn location_json(pos: Point) -> Value {
+    json!({"x": pos.x, "y": pos.y})
+}
+
+fn per_player_json<T: Into<Value>>(values: Vec<T>) -> Value {
+    Value::Object(values.into_iter().enumerate().map(|(pid, v)| (pid.to_string(), v.into())).collect())
+}
+
+// Records a local match in the official .hlt format so the Halite visualizer
+// can play it back. Call `start_turn` before and `end_turn` after every
+// `Engine::step`, then `save` once the match is over.
+pub struct ReplayWriter {
+    player_names: Vec<String>,
+    initial_energy: Vec<usize>,
+    production_map: GMap<usize>,
+    frames: Vec<Value>,
+    turn_ships: BTreeMap<usize, Ship>,
+    turn_inspired: HashSet<usize>, // ids of the ships in turn_ships the engine treats as inspired this turn
+}
+
+impl ReplayWriter {
+    pub fn new(engine: &Engine, player_names: &[String]) -> ReplayWriter {
+        let zeros = vec![0; engine.num_players];
+        ReplayWriter {
+            player_names: player_names.to_vec(),
+            initial_energy: engine.energy.to_vec(),
+            production_map: engine.halite_map.clone(),
+            frames: vec![json!({
+                "cells": [],
+                "deposited": per_player_json(zeros),
+                "energy": per_player_json(engine.energy.to_vec()),
+                "entities": {},
+                "events": [],
+                "moves": {},
+            })],
+            turn_ships: BTreeMap::new(),
+            turn_inspired: HashSet::new(),
+        }
+    }
+
+    pub fn start_turn(&mut self, engine: &Engine) {
+        self.turn_ships = engine.ships.clone();
+        self.turn_inspired = engine.ships.values()
+            .filter(|s| engine.is_inspired(s))
+            .map(|s| s.ship_id)
+            .collect();
+    }
+
+    pub fn end_turn(&mut self, engine: &Engine, player_commands: &[TurnCommands]) {
+        let mut entities = Map::new();
+        let mut moves = Map::new();
+        for (pid, &(spawn, ref commands)) in player_commands.iter().enumerate() {
+            let mut player_entities = Map::new();
+            for s in self.turn_ships.values().filter(|s| s.player == pid) {
+                player_entities.insert(s.ship_id.to_string(), json!({
+                    "x": s.pos.x, "y": s.pos.y, "energy": s.halite,
+                    "is_inspired": self.turn_inspired.contains(&s.ship_id),
+                }));
+            }
+            entities.insert(pid.to_string(), Value::Object(player_entities));
+
+            let mut ids: Vec<&usize> = commands.keys()
+                .filter(|id| self.turn_ships.get(id).is_some_and(|s| s.player == pid))
+                .collect();
+            ids.sort();
+            let mut player_moves: Vec<Value> = ids.into_iter().map(|&id| match commands[&id] {
+                    ShipCommand::MakeDropoff() => json!({"type": "c", "id": id}),
+                    ShipCommand::MoveShip(dir) => json!({"type": "m", "id": id,
+                        "direction": dir.get_char_encoding().to_string()}),
+                })
+                .collect();
+            if spawn {
+                player_moves.push(json!({"type": "g"}));
+            }
+            moves.insert(pid.to_string(), Value::Array(player_moves));
+        }
+
+        let events: Vec<Value> = engine.events().iter().map(|e| match *e {
+                Event::Spawn { player, ship_id, pos } => json!({"type": "spawn", "id": ship_id,
+                    "owner_id": player, "location": location_json(pos), "energy": 0}),
+                Event::Construct { player, dropoff_id, pos } => json!({"type": "construct", "id": dropoff_id,
+                    "owner_id": player, "location": location_json(pos)}),
+                Event::Shipwreck { pos, ref ships } => json!({"type": "shipwreck",
+                    "location": location_json(pos), "ships": ships}),
+            })
+            .collect();
+        let cells: Vec<Value> = engine.changed_cells().iter()
+            .map(|&p| json!({"x": p.x, "y": p.y, "production": engine.halite_at(p)}))
+            .collect();
+
+        self.frames.push(json!({
+            "cells": cells,
+            "deposited": per_player_json(engine.stats.iter().map(|s| s.halite_deposited).collect()),
+            "energy": per_player_json(engine.energy.to_vec()),
+            "entities": Value::Object(entities),
+            "events": events,
+            "moves": Value::Object(moves),
+        }));
+    }
+
+    pub fn to_json(&self, engine: &Engine) -> Value {
+        let players: Vec<Value> = engine.factories.iter().map(|f| json!({
+                "player_id": f.player,
+                "name": self.player_names[f.player],
+                "factory_location": location_json(f.pos),
+                "energy": self.initial_energy[f.player],
+                "entities": [],
+            }))
+            .collect();
+        let grid: Vec<Vec<Value>> = self.production_map.rows()
+            .map(|row| row.iter().map(|&h| json!({"energy": h})).collect())
+            .collect();
+
+        let mut by_energy: Vec<usize> = (0..engine.num_players).collect();
+        by_energy.sort_by_key(|&pid| ::std::cmp::Reverse(engine.energy[pid]));
+        let player_statistics: Vec<Value> = (0..engine.num_players).map(|pid| {
+                let stats = &engine.stats[pid];
+                json!({
+                    "player_id": pid,
+                    "rank": by_energy.iter().position(|&p| p == pid).unwrap() + 1,
+                    "last_turn_alive": engine.turn_number,
+                    "final_production": engine.energy[pid],
+                    "total_production": stats.halite_deposited,
+                    "number_dropoffs": stats.dropoffs_built,
+                    "ships_spawned": stats.ships_built,
+                    "all_collisions": stats.collisions,
+                })
+            })
+            .collect();
+
+        json!({
+            "ENGINE_VERSION": "my_bot selfplay",
+            "REPLAY_FILE_VERSION": 3,
+            "GAME_CONSTANTS": engine.constants.to_json(),
+            "map_generator_seed": engine.seed,
+            "number_of_players": engine.num_players,
+            "players": players,
+            "production_map": {
+                "width": engine.width,
+                "height": engine.height,
+                "grid": grid,
+                "map_generator": "fractal value noise",
+            },
+            "game_statistics": {
+                "number_turns": engine.turn_number,
+                "player_statistics": player_statistics,
+            },
+            "full_frames": self.frames,
+        })
+    }
+
+    // zstd-compressed JSON, like the replays from the official engine
+    pub fn save(&self, engine: &Engine, path: &str) -> io::Result<()> {
+        let json = self.to_json(engine).to_string();
+        let compressed = zstd::encode_all(json.as_bytes(), 0)?;
+        File::create(path)?.write_all(&compressed)
+    }
+}
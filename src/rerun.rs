@@ -0,0 +1,43 @@
+extern crate my_bot;
+extern crate simplelog;
+
+use std::env;
+use std::process;
+use simplelog::*;
+
+use my_bot::{snapshot, ShipCommand};
+
+// Re-runs make_moves on a turn snapshot written by the bot and prints the commands.
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let path = match args.iter().find(|a| !a.starts_with("--")) {
+        Some(p) => p,
+        None => {
+            eprintln!("usage: rerun SNAPSHOT [--log]");
+            process::exit(1);
+        },
+    };
+    if args.iter().any(|a| a == "--log") {
+        let _ = SimpleLogger::init(LevelFilter::Info, Config::default());
+    }
+
+    let (game, mut strategy) = snapshot::load(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let (spawn, commands) = strategy.make_moves(&game);
+
+    println!("turn {}, player {}, halite {}", game.turn_number, game.my_pid, game.energy[game.my_pid]);
+    println!("spawn: {}", spawn);
+    let mut ids: Vec<&usize> = commands.keys().collect();
+    ids.sort();
+    for id in ids {
+        let ship = &game.ships[id];
+        let command = match commands[id] {
+            ShipCommand::MakeDropoff() => "make dropoff".to_owned(),
+            ShipCommand::MoveShip(dir) => format!("move {:?}", dir),
+        };
+        println!("ship {:>4} at ({:>2}, {:>2}) carrying {:>4}: {}",
+            id, ship.pos.x, ship.pos.y, ship.halite, command);
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::BinaryHeap;
+use std::cmp::Ordering;
+use game::Game;
+use data::*;
+
+// A* over the map with flat per-cell storage that is kept between searches.
+// Instead of clearing the buffers, each search bumps `generation` and a cell only
+// counts as visited if its stamp matches.
+pub struct GridSearch {
+    width: usize,
+    height: usize,
+    generation: u32,
+    stamp: Vec<u32>, // generation in which cost/parent were last written
+    closed: Vec<u32>, // generation in which the cell was expanded
+    cost: Vec<isize>,
+    parent: Vec<usize>,
+    start: usize,
+    frontier: BinaryHeap<SearchEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SearchEntry {
+    idx: usize,
+    cost: isize, // cost when pushed; the entry is stale if the cell got cheaper since
+    w: isize,
+}
+
+impl Ord for SearchEntry {
+    fn cmp(&self, other: &SearchEntry) -> Ordering {
+        other.w.cmp(&self.w).then(other.idx.cmp(&self.idx)) // so that smaller numbers go first
+    }
+}
+
+impl PartialOrd for SearchEntry {
+    fn partial_cmp(&self, other: &SearchEntry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl GridSearch {
+    pub fn new(width: usize, height: usize) -> GridSearch {
+        GridSearch {
+            width, height,
+            generation: 0,
+            stamp: vec![0; width*height],
+            closed: vec![0; width*height],
+            cost: vec![0; width*height],
+            parent: vec![0; width*height],
+            start: 0,
+            frontier: BinaryHeap::new(),
+        }
+    }
+
+    fn idx(&self, pos: Point) -> usize {
+        pos.y as usize*self.width + pos.x as usize
+    }
+
+    fn pos(&self, idx: usize) -> Point {
+        Point { x: (idx % self.width) as isize, y: (idx / self.width) as isize }
+    }
+
+    // Searches outward from start until every cell of `until` has been expanded, or the
+    // whole map if `until` is empty. edge_cost(from, to) must be at least min_edge_cost,
+    // which scales the distance-to-`toward` heuristic; the costs of expanded cells are exact.
+    pub fn run(&mut self, game: &Game, start: Point, toward: Point, until: &[Point], min_edge_cost: isize,
+        edge_cost: &dyn Fn(Point, Point) -> isize) {
+        assert!(game.width == self.width && game.height == self.height);
+        self.generation += 1;
+        self.frontier.clear();
+        let gen = self.generation;
+        let mut remaining: Vec<usize> = until.iter().map(|&p| self.idx(p)).collect();
+
+        self.start = self.idx(start);
+        self.stamp[self.start] = gen;
+        self.cost[self.start] = 0;
+        self.parent[self.start] = self.start;
+        self.frontier.push(SearchEntry { idx: self.start, cost: 0, w: min_edge_cost*game.dist(start, toward) as isize });
+
+        while let Some(SearchEntry { idx, cost, w: _ }) = self.frontier.pop() {
+            if self.closed[idx] == gen || cost > self.cost[idx] {
+                continue; // stale entry, the cell was reached more cheaply since it was pushed
+            }
+            self.closed[idx] = gen;
+            if !until.is_empty() {
+                remaining.retain(|&i| i != idx);
+                if remaining.is_empty() {
+                    break;
+                }
+            }
+
+            let current = self.pos(idx);
+            for next in game.neighbors(current) {
+                let next_idx = self.idx(next);
+                if self.closed[next_idx] == gen {
+                    continue;
+                }
+                let new_cost = cost + edge_cost(current, next);
+                if self.stamp[next_idx] != gen || new_cost < self.cost[next_idx] {
+                    self.stamp[next_idx] = gen;
+                    self.cost[next_idx] = new_cost;
+                    self.parent[next_idx] = idx;
+                    self.frontier.push(SearchEntry {
+                        idx: next_idx,
+                        cost: new_cost,
+                        w: new_cost + min_edge_cost*game.dist(next, toward) as isize,
+                    });
+                }
+            }
+        }
+    }
+
+    // cost of the cheapest path from the last start to pos, if the last search expanded pos
+    pub fn cost(&self, pos: Point) -> Option<isize> {
+        let idx = self.idx(pos);
+        if self.closed[idx] == self.generation { Some(self.cost[idx]) } else { None }
+    }
+
+    // the cells from the last start to pos, both included
+    pub fn path(&self, pos: Point) -> Option<Vec<Point>> {
+        self.cost(pos)?;
+        let mut idx = self.idx(pos);
+        let mut path = vec![pos];
+        while idx != self.start {
+            idx = self.parent[idx];
+            path.push(self.pos(idx));
+        }
+        path.reverse();
+        Some(path)
+    }
+}
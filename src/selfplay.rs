@@ -0,0 +1,180 @@
+extern crate my_bot;
+
+use std::env;
+use std::process;
+
+use my_bot::{Engine, Constants, Factory, Game, GMap, LineReader, ReplayWriter, Strategy, TurnCommands};
+use my_bot::{mapgen, strategy};
+use my_bot::enemy_model::EnemyMoveModel;
+
+const BOT_NAME: &str = "jank_bot_17";
+
+struct Options {
+    players: usize,
+    size: usize,
+    seeds: Vec<u64>,
+    strategies: Vec<String>, // player i plays strategies[i % len]
+    map: Option<String>,
+    export_maps: Option<String>,
+    replay_dir: Option<String>,
+    enemy_model: Option<EnemyMoveModel>, // given to every bot
+}
+
+fn usage() -> ! {
+    eprintln!("usage: selfplay [--players 2|4] [--size 32|40|48|56|64] [--seeds A..B | A..=B | A]\n\
+        \x20               [--strategies NAME,NAME...] [--map FILE] [--export-maps DIR] [--replay-dir DIR]\n\
+        \x20               [--enemy-model FILE]\n\
+        strategies: {}", strategy::STRATEGY_NAMES.join(", "));
+    process::exit(1);
+}
+
+fn parse_seeds(s: &str) -> Option<Vec<u64>> {
+    if let Some(i) = s.find("..=") {
+        let a = s[..i].parse::<u64>().ok()?;
+        let b = s[(i+3)..].parse::<u64>().ok()?;
+        Some((a..=b).collect())
+    } else if let Some(i) = s.find("..") {
+        let a = s[..i].parse::<u64>().ok()?;
+        let b = s[(i+2)..].parse::<u64>().ok()?;
+        Some((a..b).collect())
+    } else {
+        s.parse::<u64>().ok().map(|a| vec![a])
+    }
+}
+
+fn parse_args() -> Options {
+    let mut options = Options { players: 2, size: 32, seeds: vec![1],
+        strategies: vec![strategy::STRATEGY_NAMES[0].to_owned()], map: None, export_maps: None, replay_dir: None,
+        enemy_model: None };
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match flag.as_str() {
+            "--players" => options.players = value.parse().unwrap_or_else(|_| usage()),
+            "--size" => options.size = value.parse().unwrap_or_else(|_| usage()),
+            "--seeds" => options.seeds = parse_seeds(&value).unwrap_or_else(|| usage()),
+            "--strategies" => options.strategies = value.split(',').map(|s| s.to_owned()).collect(),
+            "--map" => options.map = Some(value),
+            "--export-maps" => options.export_maps = Some(value),
+            "--replay-dir" => options.replay_dir = Some(value),
+            "--enemy-model" => options.enemy_model = Some(EnemyMoveModel::load(&value).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            })),
+            _ => usage(),
+        }
+    }
+    if (options.players != 2 && options.players != 4) || options.seeds.is_empty()
+        || options.strategies.iter().any(|s| !strategy::STRATEGY_NAMES.contains(&s.as_str())) {
+        usage();
+    }
+    options
+}
+
+// plays one match between fresh bots and returns the engine in its final state
+fn play_match(halite_map: GMap<usize>, factories: Vec<Factory>, strategy_names: &[&str], seed: u64,
+    enemy_model: Option<&EnemyMoveModel>, replay_path: Option<String>) -> Engine {
+    let players = factories.len();
+    let constants = Constants::defaults(halite_map.width, halite_map.height);
+    let mut engine = Engine::new(constants, factories, halite_map, seed);
+
+    let mut games: Vec<Game> = (0..players).map(|pid| engine.init_game(pid)).collect();
+    let mut bots: Vec<Box<dyn Strategy>> = strategy_names.iter()
+        .map(|name| strategy::by_name(name).unwrap())
+        .collect();
+    for (game, bot) in games.iter().zip(bots.iter_mut()) {
+        if let Some(model) = enemy_model {
+            bot.set_enemy_model(model.clone());
+        }
+        bot.on_init(game);
+    }
+    let names: Vec<String> = strategy_names.iter().map(|name| format!("{} ({})", BOT_NAME, name)).collect();
+    let mut replay = replay_path.as_ref().map(|_| ReplayWriter::new(&engine, &names));
+
+    while !engine.is_over() {
+        let frame = engine.frame_lines();
+        let mut commands: Vec<TurnCommands> = Vec::new();
+        for (game, bot) in games.iter_mut().zip(bots.iter_mut()) {
+            game.update_frame(&mut LineReader::new(frame.iter().cloned()))
+                .expect("engine wrote an invalid frame");
+            commands.push(bot.make_moves(game));
+        }
+        if let Some(ref mut r) = replay {
+            r.start_turn(&engine);
+        }
+        engine.step(&commands);
+        if let Some(ref mut r) = replay {
+            r.end_turn(&engine, &commands);
+        }
+    }
+
+    if let (Some(r), Some(path)) = (replay, replay_path) {
+        if let Err(e) = r.save(&engine, &path) {
+            eprintln!("could not write replay {}: {}", path, e);
+        }
+    }
+    engine
+}
+
+fn main() {
+    let mut options = parse_args();
+    let fixed_map = options.map.as_ref().map(|path| mapgen::load(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    }));
+    if let Some((_, ref factories)) = fixed_map {
+        options.players = factories.len();
+    }
+    let n = options.players;
+    let strategy_names: Vec<&str> = (0..n)
+        .map(|pid| options.strategies[pid % options.strategies.len()].as_str())
+        .collect();
+
+    println!("{:>6} {:>6} {:>16} {:>10} {:>6} {:>9} {:>11}",
+        "seed", "player", "strategy", "halite", "ships", "dropoffs", "collisions");
+    let mut wins = vec![0; n];
+    let mut total_halite = vec![0; n];
+    for &seed in &options.seeds {
+        let (halite_map, factories) = match fixed_map {
+            Some((ref halite_map, ref factories)) => (halite_map.clone(), factories.to_vec()),
+            None => mapgen::generate(n, options.size, options.size, seed),
+        };
+        if let Some(ref dir) = options.export_maps {
+            let path = format!("{}/map-{}.txt", dir, seed);
+            if let Err(e) = mapgen::save(&path, &halite_map, &factories) {
+                eprintln!("could not export {}: {}", path, e);
+            }
+        }
+        let replay_path = options.replay_dir.as_ref().map(|dir| format!("{}/replay-{}.hlt", dir, seed));
+        let engine = play_match(halite_map, factories, &strategy_names, seed, options.enemy_model.as_ref(), replay_path);
+        for (pid, stats) in engine.stats.iter().enumerate() {
+            println!("{:>6} {:>6} {:>16} {:>10} {:>6} {:>9} {:>11}", seed, pid, strategy_names[pid],
+                engine.energy[pid], stats.ships_built, stats.dropoffs_built, stats.collisions);
+            total_halite[pid] += engine.energy[pid];
+        }
+        let winner = (0..n).max_by_key(|&pid| engine.energy[pid]).unwrap();
+        wins[winner] += 1;
+    }
+
+    println!();
+    println!("{:>6} {:>16} {:>6} {:>12}", "player", "strategy", "wins", "mean halite");
+    for pid in 0..n {
+        println!("{:>6} {:>16} {:>6} {:>12}", pid, strategy_names[pid], wins[pid], total_halite[pid]/options.seeds.len());
+    }
+
+    // pooled over the seats each strategy played
+    let mut distinct = strategy_names.clone();
+    distinct.sort();
+    distinct.dedup();
+    if distinct.len() > 1 {
+        println!();
+        println!("{:>16} {:>6} {:>6} {:>12}", "strategy", "seats", "wins", "mean halite");
+        for name in distinct.iter() {
+            let seats: Vec<usize> = (0..n).filter(|&pid| strategy_names[pid] == *name).collect();
+            let seat_wins: usize = seats.iter().map(|&pid| wins[pid]).sum();
+            let seat_halite: usize = seats.iter().map(|&pid| total_halite[pid]).sum();
+            println!("{:>16} {:>6} {:>6} {:>12}", name, seats.len(), seat_wins,
+                seat_halite/(seats.len()*options.seeds.len()));
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::io;
+use serde_json::Value;
+use game::Game;
+use strategy::{self, Strategy};
+
+// A single turn as the bot saw it: the parsed Game plus the strategy state from
+// the previous turns, enough to call make_moves again and get the same answer.
+pub fn save(path: &str, game: &Game, strategy_name: &str, strategy: &dyn Strategy) -> io::Result<()> {
+    let snapshot = json!({
+        "game": game.to_json(),
+        "strategy": strategy_name,
+        "state": strategy.to_json(),
+    });
+    File::create(path)?.write_all(snapshot.to_string().as_bytes())
+}
+
+pub fn load(path: &str) -> Result<(Game, Box<dyn Strategy>), String> {
+    let mut text = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut text))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let snapshot: Value = ::serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+    let game = Game::from_json(&snapshot["game"]).map_err(|e| format!("{}: {}", path, e))?;
+    let name = snapshot["strategy"].as_str().ok_or_else(|| format!("{}: no strategy name", path))?;
+    let mut strategy = strategy::by_name(name).ok_or_else(|| format!("{}: unknown strategy {}", path, name))?;
+    strategy.load_json(&snapshot["state"]).map_err(|e| format!("{}: {}", path, e))?;
+    Ok((game, strategy))
+}
@@ -0,0 +1,58 @@
+use serde_json::Value;
+use game::Game;
+use data::TurnCommands;
+use bot_logic::Logic;
+use assignment::AssignMethod;
+use enemy_model::EnemyMoveModel;
+
+// names accepted by by_name, the first one is the default
+pub const STRATEGY_NAMES: &[&str] = &["jank", "jank-hungarian", "jank-flow", "jank-whca"];
+
+// A bot: gets the pre-game once, then answers every turn.
+pub trait Strategy {
+    // called after the pre-game has been read, before the bot name is sent
+    fn on_init(&mut self, _game: &Game) {}
+
+    fn make_moves(&mut self, game: &Game) -> TurnCommands;
+
+    // fitted weights for predicting enemy moves, for bots that can use them
+    fn set_enemy_model(&mut self, _model: EnemyMoveModel) {}
+
+    // state carried from turn to turn, for turn snapshots
+    fn to_json(&self) -> Value {
+        Value::Null
+    }
+
+    fn load_json(&mut self, _v: &Value) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl Strategy for Logic {
+    fn make_moves(&mut self, game: &Game) -> TurnCommands {
+        Logic::make_moves(self, game)
+    }
+
+    fn set_enemy_model(&mut self, model: EnemyMoveModel) {
+        Logic::set_enemy_model(self, model)
+    }
+
+    fn to_json(&self) -> Value {
+        Logic::to_json(self)
+    }
+
+    fn load_json(&mut self, v: &Value) -> Result<(), String> {
+        *self = Logic::from_json(v)?;
+        Ok(())
+    }
+}
+
+pub fn by_name(name: &str) -> Option<Box<dyn Strategy>> {
+    match name {
+        "jank" => Some(Box::new(Logic::new())),
+        "jank-hungarian" => Some(Box::new(Logic::with_assignment(AssignMethod::Hungarian))),
+        "jank-flow" => Some(Box::new(Logic::with_assignment(AssignMethod::MinCostFlow))),
+        "jank-whca" => Some(Box::new(Logic::with_cooperative_moves(6))),
+        _ => None,
+    }
+}